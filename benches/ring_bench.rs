@@ -1,11 +1,11 @@
-use criterion::{criterion_group, criterion_main, Criterion};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use std::thread;
 
 fn exact_ring(c: &mut Criterion) {
     c.bench_function("exact ring", |b| {
-        b.iter(|| async move {
-            let (mut tx, rx) = spsc_rs::exact_channel(65536);
-
-            std::mem::forget(rx);
+        b.iter(|| {
+            // the items are dropped with the channel at the end of each iteration.
+            let (mut tx, _rx) = spsc_rs::exact_channel(65536);
 
             for i in 0..65536 {
                 tx.start_send(i).unwrap();
@@ -16,10 +16,9 @@ fn exact_ring(c: &mut Criterion) {
 
 fn ring(c: &mut Criterion) {
     c.bench_function("ring", |b| {
-        b.iter(|| async move {
-            let (mut tx, rx) = spsc_rs::channel(65536);
-
-            std::mem::forget(rx);
+        b.iter(|| {
+            // the items are dropped with the channel at the end of each iteration.
+            let (mut tx, _rx) = spsc_rs::channel(65536);
 
             for i in 0..65536 {
                 tx.start_send(i).unwrap();
@@ -28,5 +27,106 @@ fn ring(c: &mut Criterion) {
     });
 }
 
-criterion_group!(ring_bench, exact_ring, ring);
+/// Sends and receives in batches of 64 on one thread, so the positions are always
+/// in the cache and only the cost of the position checks is measured.
+fn ring_send_recv(c: &mut Criterion) {
+    let (mut tx, mut rx) = spsc_rs::channel(1024);
+    c.bench_function("ring send recv", |b| {
+        b.iter(|| {
+            for _ in 0..1024 {
+                for i in 0..64 {
+                    tx.try_send(i).unwrap();
+                }
+                for _ in 0..64 {
+                    black_box(rx.try_recv().unwrap());
+                }
+            }
+        })
+    });
+}
+
+/// `ring_batch` on an exact channel whose capacity isn't a power of two, so the indexing
+/// is most of the work.
+fn exact_ring_batch(c: &mut Criterion) {
+    let (mut tx, mut rx) = spsc_rs::exact_channel(1000);
+    rx.set_batch_recv(true);
+    c.bench_function("exact ring batch", |b| {
+        b.iter(|| {
+            for _ in 0..1024 {
+                for i in 0..64 {
+                    tx.start_send(i).unwrap();
+                }
+                tx.commit();
+                for _ in 0..64 {
+                    black_box(rx.try_recv().unwrap());
+                }
+                rx.commit();
+            }
+        })
+    });
+}
+
+/// The same batches as `ring_send_recv`, but each side publishes its position once per batch.
+fn ring_batch(c: &mut Criterion) {
+    let (mut tx, mut rx) = spsc_rs::channel(1024);
+    tx.set_batch_send(true);
+    rx.set_batch_recv(true);
+    c.bench_function("ring batch", |b| {
+        b.iter(|| {
+            for _ in 0..1024 {
+                for i in 0..64 {
+                    tx.start_send(i).unwrap();
+                }
+                tx.commit();
+                for _ in 0..64 {
+                    black_box(rx.try_recv().unwrap());
+                }
+                rx.commit();
+            }
+        })
+    });
+}
+
+/// Streams items from another thread, where the positions bounce between the cores.
+///
+/// Only meaningful with at least two cores: on a single one, the threads just take turns
+/// and no cache line moves.
+fn ring_threads(c: &mut Criterion) {
+    const AMT: u32 = 1 << 16;
+
+    c.bench_function("ring threads", |b| {
+        b.iter(|| {
+            let (mut tx, mut rx) = spsc_rs::channel(1024);
+            let handle = thread::spawn(move || {
+                for i in 0..AMT {
+                    while tx.try_send(i).is_err() {
+                        thread::yield_now();
+                    }
+                }
+            });
+            for _ in 0..AMT {
+                loop {
+                    match rx.try_recv() {
+                        Ok(item) => {
+                            black_box(item);
+                            break;
+                        }
+                        Err(_) => thread::yield_now(),
+                    }
+                }
+            }
+            handle.join().unwrap();
+        })
+    });
+}
+
+criterion_group!(
+    ring_bench,
+    exact_ring,
+    ring,
+    ring_send_recv,
+    ring_batch,
+    exact_ring_batch,
+    ring_threads
+);
 criterion_main!(ring_bench);
@@ -12,13 +12,13 @@ fn no_contention_spsc(c: &mut Criterion) {
         b.iter(|| {
             let (mut tx, mut rx) = spsc_rs::channel(4096);
 
-            let _ = rt.block_on(async move {
+            rt.block_on(async move {
                 for i in 0..4096 {
                     tx.send(i).await.unwrap();
                 }
             });
 
-            let _ = rt.block_on(async move {
+            rt.block_on(async move {
                 for _ in 0..4096 {
                     rx.recv().await;
                 }
@@ -33,13 +33,13 @@ fn no_contention_mpsc(c: &mut Criterion) {
         b.iter(|| {
             let (tx, mut rx) = tokio_mpsc::channel(4096);
 
-            let _ = rt.block_on(async move {
+            rt.block_on(async move {
                 for i in 0..4096 {
                     tx.send(i).await.unwrap();
                 }
             });
 
-            let _ = rt.block_on(async move {
+            rt.block_on(async move {
                 for _ in 0..4096 {
                     rx.recv().await;
                 }
@@ -1,15 +1,19 @@
-use spsc_rs::error::SendError;
-
 #[tokio::main]
 async fn main() {
     let (mut tx, mut rx) = spsc_rs::channel(128);
 
     tokio::spawn(async move {
         for i in 0..1024 {
-            match tx.start_send(i) {
-                Ok(_) => {}
-                Err(SendError::Full) => tx.flush().await?,
-                Err(e) => return Err(e),
+            let mut item = i;
+            loop {
+                match tx.start_send(item) {
+                    Ok(_) => break,
+                    Err(e) if e.is_full() => {
+                        item = e.into_inner();
+                        tx.flush().await?;
+                    }
+                    Err(e) => return Err(e.into_send_error()),
+                }
             }
         }
 
@@ -0,0 +1,264 @@
+//! Custom memory for the ring of a bounded channel and the blocks of an unbounded one.
+//!
+//! [`channel_with_alloc`], [`exact_channel_with_alloc`] and [`unbounded_channel_with_alloc`]
+//! take an [`Allocator`], which allocates the ring once when the channel is created, and each
+//! block of 128 items of the unbounded channel when the sender needs it.
+//!
+//! On Linux, the `mmap` feature adds `MmapAllocator`, which maps the memory directly, so it can
+//! be backed by huge pages, prefaulted and locked in RAM. A real-time thread then never takes
+//! a page fault on the first touch of a slot.
+//!
+//! [`channel_with_alloc`]: crate::channel_with_alloc
+//! [`exact_channel_with_alloc`]: crate::exact_channel_with_alloc
+//! [`unbounded_channel_with_alloc`]: crate::unbounded_channel_with_alloc
+
+#[cfg(not(loom))]
+use alloc::boxed::Box;
+use core::alloc::Layout;
+use core::fmt;
+#[cfg(not(loom))]
+use core::marker::PhantomData;
+#[cfg(not(loom))]
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+/// The allocator failed to provide the memory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AllocError;
+
+impl fmt::Display for AllocError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "memory allocation failed".fmt(f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for AllocError {}
+
+/// Allocates the memory behind a channel.
+///
+/// Both sides of the channel may call the allocator, the sender of an unbounded channel
+/// allocates the blocks and its receiver frees them. The allocator is never called with
+/// a zero sized layout.
+///
+/// # Safety
+///
+/// `allocate` must return memory which fits `layout`, and which stays valid until it is
+/// given back to `deallocate` with the same layout.
+pub unsafe trait Allocator: Send + Sync + 'static {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError>;
+
+    /// # Safety
+    ///
+    /// `ptr` must be returned by `allocate` with the same `layout`.
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout);
+}
+
+/// The global allocator, which the channels use by default.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Global;
+
+unsafe impl Allocator for Global {
+    fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+        NonNull::new(unsafe { alloc::alloc::alloc(layout) }).ok_or(AllocError)
+    }
+
+    unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+        alloc::alloc::dealloc(ptr.as_ptr(), layout)
+    }
+}
+
+/// `cap` uninitialized items from an [`Allocator`], which are given back on drop.
+#[cfg(not(loom))]
+pub(crate) struct AllocBuf<T> {
+    ptr: NonNull<MaybeUninit<T>>,
+    cap: usize,
+    alloc: Box<dyn Allocator>,
+    _marker: PhantomData<T>,
+}
+
+#[cfg(not(loom))]
+impl<T> AllocBuf<T> {
+    pub(crate) fn new(cap: usize, alloc: Box<dyn Allocator>) -> Result<Self, AllocError> {
+        let layout = Layout::array::<T>(cap).map_err(|_| AllocError)?;
+        let ptr = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            alloc.allocate(layout)?.cast()
+        };
+        Ok(Self {
+            ptr,
+            cap,
+            alloc,
+            _marker: PhantomData,
+        })
+    }
+
+    pub(crate) fn as_ptr(&self) -> *mut MaybeUninit<T> {
+        self.ptr.as_ptr()
+    }
+
+    pub(crate) fn cap(&self) -> usize {
+        self.cap
+    }
+}
+
+#[cfg(not(loom))]
+impl<T> Drop for AllocBuf<T> {
+    fn drop(&mut self) {
+        let layout = Layout::array::<T>(self.cap).unwrap();
+        if layout.size() > 0 {
+            unsafe { self.alloc.deallocate(self.ptr.cast(), layout) }
+        }
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "mmap", not(loom)))]
+pub use self::mmap::{HugePages, MmapAllocator};
+
+#[cfg(all(target_os = "linux", feature = "mmap", not(loom)))]
+mod mmap {
+    use super::{AllocError, Allocator};
+    use core::alloc::Layout;
+    use core::ptr::{self, NonNull};
+    use std::sync::OnceLock;
+
+    /// Which pages back the memory of a [`MmapAllocator`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+    pub enum HugePages {
+        /// Normal pages.
+        #[default]
+        Off,
+        /// Transparent huge pages, asked with `madvise(MADV_HUGEPAGE)`. The kernel may still
+        /// use normal pages, for example when THP is disabled.
+        Transparent,
+        /// Pages of the default huge page size from the `hugetlbfs` pool, mapped with
+        /// `MAP_HUGETLB`. The allocation fails if the pool has not enough free pages.
+        Explicit,
+    }
+
+    /// Maps the memory of a channel with `mmap`, see the [module docs](super).
+    ///
+    /// Every allocation is rounded up to whole pages, so it suits the ring of a bounded
+    /// channel much better than the small blocks of an unbounded one.
+    ///
+    /// ```no_run
+    /// use spsc_rs::allocator::{HugePages, MmapAllocator};
+    ///
+    /// let alloc = MmapAllocator::new()
+    ///     .huge_pages(HugePages::Transparent)
+    ///     .prefault(true)
+    ///     .mlock(true);
+    /// let (tx, rx) = spsc_rs::channel_with_alloc::<u64, _>(1 << 20, alloc).unwrap();
+    /// ```
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct MmapAllocator {
+        huge_pages: HugePages,
+        prefault: bool,
+        mlock: bool,
+    }
+
+    impl MmapAllocator {
+        pub const fn new() -> Self {
+            Self {
+                huge_pages: HugePages::Off,
+                prefault: false,
+                mlock: false,
+            }
+        }
+
+        pub const fn huge_pages(mut self, huge_pages: HugePages) -> Self {
+            self.huge_pages = huge_pages;
+            self
+        }
+
+        /// Faults all the pages in with `MAP_POPULATE` when the memory is mapped.
+        pub const fn prefault(mut self, prefault: bool) -> Self {
+            self.prefault = prefault;
+            self
+        }
+
+        /// Locks the pages in RAM with `mlock`, which also faults them in. The allocation
+        /// fails if the pages can't be locked, for example over `RLIMIT_MEMLOCK`.
+        pub const fn mlock(mut self, mlock: bool) -> Self {
+            self.mlock = mlock;
+            self
+        }
+
+        fn map_len(&self, layout: Layout) -> Result<usize, AllocError> {
+            let page = match self.huge_pages {
+                HugePages::Explicit => huge_page_size(),
+                HugePages::Off | HugePages::Transparent => page_size(),
+            };
+            if layout.align() > page {
+                return Err(AllocError);
+            }
+            layout
+                .size()
+                .checked_next_multiple_of(page)
+                .ok_or(AllocError)
+        }
+    }
+
+    unsafe impl Allocator for MmapAllocator {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+            let len = self.map_len(layout)?;
+            let mut flags = libc::MAP_PRIVATE | libc::MAP_ANONYMOUS;
+            if self.prefault {
+                flags |= libc::MAP_POPULATE;
+            }
+            if self.huge_pages == HugePages::Explicit {
+                flags |= libc::MAP_HUGETLB;
+            }
+
+            unsafe {
+                let ptr = libc::mmap(
+                    ptr::null_mut(),
+                    len,
+                    libc::PROT_READ | libc::PROT_WRITE,
+                    flags,
+                    -1,
+                    0,
+                );
+                if ptr == libc::MAP_FAILED {
+                    return Err(AllocError);
+                }
+
+                if self.huge_pages == HugePages::Transparent {
+                    // only a hint, the memory is usable without huge pages.
+                    libc::madvise(ptr, len, libc::MADV_HUGEPAGE);
+                }
+                if self.mlock && libc::mlock(ptr, len) != 0 {
+                    libc::munmap(ptr, len);
+                    return Err(AllocError);
+                }
+                Ok(NonNull::new_unchecked(ptr.cast()))
+            }
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            let len = self.map_len(layout).unwrap();
+            // unmapping also unlocks the pages.
+            libc::munmap(ptr.as_ptr().cast(), len);
+        }
+    }
+
+    fn page_size() -> usize {
+        unsafe { libc::sysconf(libc::_SC_PAGESIZE) as usize }
+    }
+
+    /// The default huge page size from `/proc/meminfo`, which `MAP_HUGETLB` uses.
+    fn huge_page_size() -> usize {
+        static SIZE: OnceLock<usize> = OnceLock::new();
+        *SIZE.get_or_init(|| {
+            std::fs::read_to_string("/proc/meminfo")
+                .ok()
+                .and_then(|info| {
+                    let line = info.lines().find(|l| l.starts_with("Hugepagesize:"))?;
+                    let kb = line.split_whitespace().nth(1)?.parse::<usize>().ok()?;
+                    Some(kb * 1024)
+                })
+                .unwrap_or(2 << 20)
+        })
+    }
+}
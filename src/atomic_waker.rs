@@ -1,7 +1,9 @@
+#[cfg(loom)]
+use crate::loom::thread;
 use crate::loom::UnsafeCell;
-use crate::loom::{thread, AtomicU8, Ordering};
-use core::ptr;
-use std::task::{RawWaker, RawWakerVTable, Waker};
+use crate::loom::{AtomicU8, Ordering};
+use core::task::{RawWaker, RawWakerVTable, Waker};
+use core::{mem, ptr};
 
 /// A waker for single producer and single consumer.
 ///
@@ -23,20 +25,24 @@ const FULL: u8 = WAKING | REGISTERING;
 
 impl AtomicWaker {
     pub(crate) fn register(&self, waker: &Waker) {
-        match self.state.fetch_or(REGISTERING, Ordering::AcqRel) {
+        // We only take the REGISTERING bit when nobody is waking. Otherwise a second wake could
+        // see REGISTERING after the first one cleared WAKING, leave its WAKING bit for us, and
+        // the state would stay in WAKING forever.
+        match self
+            .state
+            .compare_exchange(WAITING, REGISTERING, Ordering::AcqRel, Ordering::Acquire)
+            .unwrap_or_else(|state| state)
+        {
             WAKING => {
                 // the waker is waking now, we just wake this waker.
                 waker.wake_by_ref();
 
-                self.state.fetch_and(!REGISTERING, Ordering::Release);
-
                 // loom's scheduler is not fair, so we need to manually yield in loom
                 // to avoid infinitely wakeup.
                 //
                 // see https://docs.rs/loom/0.5.2/loom/#yielding
-                if_loom! {
-                    thread::yield_now();
-                }
+                #[cfg(loom)]
+                thread::yield_now();
             }
             state => {
                 debug_assert_eq!(state, WAITING);
@@ -83,14 +89,18 @@ impl AtomicWaker {
     pub(crate) fn wake_by_ref(&self) {
         match self.state.fetch_or(WAKING, Ordering::AcqRel) {
             WAITING => {
-                self.waker.with(|ptr| {
+                let waker = self.waker.with_mut(|ptr| {
                     // Safety: we hold the WAKING bit, so there is no
                     // register operating on the cell.
-                    let inner_waker = unsafe { &*ptr };
-                    inner_waker.wake_by_ref();
+                    let inner_waker = unsafe { &mut *ptr };
+                    mem::replace(inner_waker, dummy_waker())
                 });
 
                 self.state.fetch_and(!WAKING, Ordering::Release);
+
+                // Wake outside of the WAKING window. If this thread is preempted inside `wake`,
+                // the other side would otherwise spin in `register` until we are scheduled again.
+                waker.wake();
             }
             state => {
                 // fail to hold the WAKING bit, we just let the register call wake method.
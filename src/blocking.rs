@@ -0,0 +1,108 @@
+use crate::loom::thread::{self, Thread};
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::time::{Duration, Instant};
+
+/// Unpark the blocked thread when the other side wakes us through the `AtomicWaker`.
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+#[cfg(not(loom))]
+fn current_waker() -> Waker {
+    std::thread_local! {
+        // Keep one waker per thread, so a thread which blocks on the channel repeatedly doesn't
+        // allocate a new one each time. Registering it only clones the `Arc`.
+        static WAKER: Waker = Arc::new(ThreadWaker(thread::current())).into();
+    }
+
+    WAKER.with(|waker| waker.clone())
+}
+
+#[cfg(loom)]
+fn current_waker() -> Waker {
+    Arc::new(ThreadWaker(thread::current())).into()
+}
+
+#[cfg(not(loom))]
+fn park_timeout(dur: Duration) {
+    thread::park_timeout(dur)
+}
+
+#[cfg(loom)]
+fn park_timeout(_: Duration) {
+    // loom has no notion of time, we just give the other threads a chance to run.
+    thread::yield_now()
+}
+
+/// Parks the current thread until it is unparked, or the timeout elapses.
+pub(crate) fn park(timeout: Option<Duration>) {
+    match timeout {
+        None => thread::park(),
+        Some(timeout) => park_timeout(timeout),
+    }
+}
+
+/// Drive `poll` on the current thread, parking it until the registered waker is woken.
+pub(crate) fn block_on<T>(poll: impl FnMut(&mut Context<'_>) -> Poll<T>) -> T {
+    block_on_with(park, poll)
+}
+
+/// The same as [`block_on`], but waits with `park` instead of parking the thread.
+pub(crate) fn block_on_with<T>(
+    park: impl Fn(Option<Duration>),
+    mut poll: impl FnMut(&mut Context<'_>) -> Poll<T>,
+) -> T {
+    let waker = current_waker();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        match poll(&mut cx) {
+            Poll::Ready(t) => return t,
+            // A spurious unpark just makes us poll again.
+            Poll::Pending => park(None),
+        }
+    }
+}
+
+/// The same as [`block_on`], but gives up and returns `None` once `timeout` has elapsed.
+pub(crate) fn block_on_timeout<T>(
+    timeout: Duration,
+    poll: impl FnMut(&mut Context<'_>) -> Poll<T>,
+) -> Option<T> {
+    block_on_timeout_with(park, timeout, poll)
+}
+
+/// The same as [`block_on_timeout`], but waits with `park` instead of parking the thread.
+pub(crate) fn block_on_timeout_with<T>(
+    park: impl Fn(Option<Duration>),
+    timeout: Duration,
+    mut poll: impl FnMut(&mut Context<'_>) -> Poll<T>,
+) -> Option<T> {
+    let deadline = Instant::now().checked_add(timeout);
+    let waker = current_waker();
+    let mut cx = Context::from_waker(&waker);
+    loop {
+        if let Poll::Ready(t) = poll(&mut cx) {
+            return Some(t);
+        }
+
+        match deadline {
+            None => park(None),
+            Some(deadline) => {
+                let now = Instant::now();
+                if now >= deadline {
+                    return None;
+                }
+                park(Some(deadline - now));
+            }
+        }
+    }
+}
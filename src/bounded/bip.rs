@@ -0,0 +1,298 @@
+use crate::bounded::{channel, P2Receiver, P2Sender};
+use crate::error::{SendError, TryRecvError};
+use crate::wait::WaitStrategy;
+use core::cmp;
+use core::mem::MaybeUninit;
+use core::ops::{Deref, DerefMut};
+use core::task::{Context, Poll};
+use futures_util::future::poll_fn;
+
+/// Each record starts with its length in a native endian `u32`.
+const HEADER: usize = 4;
+
+/// The header of the gap left at the end of the ring, when a record doesn't fit there.
+const SKIP: u32 = u32::MAX;
+
+/// Records are padded to the header size, so a header never wraps around the ring.
+fn record_size(len: usize) -> usize {
+    HEADER + ((len + HEADER - 1) & !(HEADER - 1))
+}
+
+/// Creates a bipartite buffer channel for variable-length byte records, which can buffer
+/// at least `size` bytes.
+///
+/// Every record is kept contiguous in the ring: when a record doesn't fit before the end of
+/// the ring, the gap is skipped and the record starts over at the beginning. So the sender
+/// writes each record in place through one `&mut [u8]`, and the receiver reads it through one
+/// `&[u8]`, without any allocation.
+pub fn bip_channel(size: usize) -> (BipSender, BipReceiver) {
+    // Make sure there is room for at least one header and a few bytes.
+    let (tx, rx) = channel(cmp::max(size, 2 * HEADER));
+
+    // Zero the ring once, so every byte is initialized and can be handed out as `u8`.
+    unsafe {
+        let ring = &tx.inner.ring;
+        let (left, _) = ring.slots_mut(0, ring.capacity());
+        left.fill(MaybeUninit::new(0));
+    }
+
+    (BipSender { inner: tx }, BipReceiver { inner: rx })
+}
+
+/// The sending half of a [`bip_channel`].
+pub struct BipSender {
+    inner: P2Sender<u8>,
+}
+
+impl BipSender {
+    /// The longest record this channel can carry.
+    ///
+    /// A record is never split, so it can only take half of the ring in the worst case.
+    pub fn max_record_len(&self) -> usize {
+        self.inner.inner.ring.capacity() / 2 - HEADER
+    }
+
+    /// Reserves a contiguous record of `len` bytes without waiting.
+    ///
+    /// Returns `Err(SendError::Full)` if there isn't enough free space now.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is bigger than [`max_record_len`].
+    ///
+    /// [`max_record_len`]: BipSender::max_record_len
+    pub fn try_reserve(&mut self, len: usize) -> Result<RecordSlot<'_>, SendError> {
+        self.check_len(len);
+
+        if self.inner.is_closed() {
+            Err(SendError::Disconnected)
+        } else if self.has_room(len) {
+            Ok(RecordSlot::new(self, len))
+        } else {
+            Err(SendError::Full)
+        }
+    }
+
+    /// Reserves a contiguous record of `len` bytes, waiting for free space as needed.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `len` is bigger than [`max_record_len`].
+    ///
+    /// [`max_record_len`]: BipSender::max_record_len
+    pub async fn reserve(&mut self, len: usize) -> Result<RecordSlot<'_>, SendError> {
+        self.check_len(len);
+        poll_fn(|cx| self.poll_room(cx, len)).await?;
+        Ok(RecordSlot::new(self, len))
+    }
+
+    /// Returns whether this channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    fn check_len(&self, len: usize) {
+        assert!(
+            len <= self.max_record_len(),
+            "record is longer than max_record_len"
+        );
+    }
+
+    /// Whether there is room for the record, and the gap to skip before it.
+    fn has_room(&self, len: usize) -> bool {
+        let ring = &self.inner.inner.ring;
+        ring.vacant_len() >= self.gap(len) + record_size(len)
+    }
+
+    /// The free bytes at the end of the ring to skip, if the record doesn't fit there.
+    fn gap(&self, len: usize) -> usize {
+        let ring = &self.inner.inner.ring;
+        let tail = ring.capacity() - ring.index(ring.write_pos());
+        if tail >= record_size(len) {
+            0
+        } else {
+            tail
+        }
+    }
+
+    fn poll_room(&mut self, cx: &mut Context<'_>, len: usize) -> Poll<Result<(), SendError>> {
+        if self.inner.is_closed() {
+            return Poll::Ready(Err(SendError::Disconnected));
+        }
+
+        if self.has_room(len) {
+            return Poll::Ready(Ok(()));
+        }
+
+        self.inner.inner.producer.register(cx.waker());
+
+        // The same as `Sender::poll_next_pos`, check again after registering.
+        if self.inner.is_closed() {
+            Poll::Ready(Err(SendError::Disconnected))
+        } else if self.has_room(len) {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
+/// A record reserved by [`BipSender::reserve`], which derefs to its bytes.
+///
+/// The record is only visible to the receiver after [`commit`]. If the slot is dropped
+/// without committing, nothing is sent.
+///
+/// [`commit`]: RecordSlot::commit
+pub struct RecordSlot<'a> {
+    sender: &'a mut BipSender,
+    gap: usize,
+    len: usize,
+}
+
+impl<'a> RecordSlot<'a> {
+    fn new(sender: &'a mut BipSender, len: usize) -> Self {
+        let gap = sender.gap(len);
+        let ring = &sender.inner.inner.ring;
+        let pos = ring.write_pos();
+
+        // Safety: the free space is checked by the caller, and only the producer
+        // writes after the producer position.
+        unsafe {
+            if gap > 0 {
+                let (slot, _) = ring.slots_mut(pos, HEADER);
+                write_header(slot, SKIP);
+            }
+            let (slot, _) = ring.slots_mut(ring.advance(pos, gap), HEADER);
+            write_header(slot, len as u32);
+        }
+
+        Self { sender, gap, len }
+    }
+
+    /// Publishes the record to the receiver.
+    pub fn commit(self) {
+        let ring = &self.sender.inner.inner.ring;
+        unsafe {
+            ring.publish(self.gap + record_size(self.len));
+        }
+        self.sender.inner.inner.consumer.wake_by_ref();
+    }
+
+    fn bytes(&self) -> *mut u8 {
+        let ring = &self.sender.inner.inner.ring;
+        let pos = ring.advance(ring.write_pos(), self.gap + HEADER);
+        // Safety: the record never wraps around, so the bytes are in the first slice.
+        unsafe { ring.slots_mut(pos, self.len).0.as_mut_ptr().cast() }
+    }
+}
+
+impl Deref for RecordSlot<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        // Safety: the ring is zeroed on creation, so all the bytes are initialized.
+        unsafe { core::slice::from_raw_parts(self.bytes(), self.len) }
+    }
+}
+
+impl DerefMut for RecordSlot<'_> {
+    fn deref_mut(&mut self) -> &mut [u8] {
+        unsafe { core::slice::from_raw_parts_mut(self.bytes(), self.len) }
+    }
+}
+
+/// The receiving half of a [`bip_channel`].
+pub struct BipReceiver {
+    inner: P2Receiver<u8>,
+}
+
+impl BipReceiver {
+    /// Returns the next record without waiting. The record is popped when the returned
+    /// guard is dropped.
+    pub fn try_recv_ref(&mut self) -> Result<RecordGuard<'_>, TryRecvError> {
+        if let Some(len) = self.next_record() {
+            return Ok(RecordGuard::new(self, len));
+        }
+
+        // The same as `Receiver::try_recv`, check the ring again after seeing the channel closed.
+        if !self.inner.is_closed() {
+            Err(TryRecvError::Empty)
+        } else if let Some(len) = self.next_record() {
+            Ok(RecordGuard::new(self, len))
+        } else {
+            Err(TryRecvError::Disconnected)
+        }
+    }
+
+    /// Waits for the next record. The record is popped when the returned guard is dropped.
+    ///
+    /// `None` is returned once the channel is closed and all the remaining records are consumed.
+    pub async fn recv_ref(&mut self) -> Option<RecordGuard<'_>> {
+        if !poll_fn(|cx| self.inner.poll_readable(cx)).await {
+            return None;
+        }
+
+        // The gap and the record after it are published together.
+        self.next_record()
+            .map(move |len| RecordGuard::new(self, len))
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    pub fn close(&mut self) {
+        self.inner.close()
+    }
+
+    /// Skips the gap at the end of the ring, and returns the length of the head record.
+    fn next_record(&mut self) -> Option<usize> {
+        loop {
+            let (left, _) = self.inner.read_slices();
+            if left.is_empty() {
+                return None;
+            }
+
+            let header = u32::from_ne_bytes(left[..HEADER].try_into().unwrap());
+            if header == SKIP {
+                // the gap always reaches the end of the ring.
+                let gap = left.len();
+                self.inner.consume(gap);
+            } else {
+                return Some(header as usize);
+            }
+        }
+    }
+}
+
+/// The head record of a [`bip_channel`], which derefs to its bytes.
+pub struct RecordGuard<'a> {
+    receiver: &'a mut BipReceiver,
+    len: usize,
+}
+
+impl<'a> RecordGuard<'a> {
+    fn new(receiver: &'a mut BipReceiver, len: usize) -> Self {
+        Self { receiver, len }
+    }
+}
+
+impl Deref for RecordGuard<'_> {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.receiver.inner.read_slices().0[HEADER..HEADER + self.len]
+    }
+}
+
+impl Drop for RecordGuard<'_> {
+    fn drop(&mut self) {
+        self.receiver.inner.consume(record_size(self.len));
+    }
+}
+
+fn write_header(slot: &mut [MaybeUninit<u8>], header: u32) {
+    for (slot, byte) in slot.iter_mut().zip(header.to_ne_bytes()) {
+        slot.write(byte);
+    }
+}
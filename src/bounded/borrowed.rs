@@ -0,0 +1,137 @@
+use crate::bounded::ring::{ExactRing, Remainder};
+use crate::bounded::{
+    from_shared, ExactReceiver, ExactSender, Receiver, Sender, Shared, SharedRef,
+};
+use crate::loom::{AtomicBool, AtomicPosition, Ordering};
+use crate::wait::AsyncWaker;
+use core::cell::UnsafeCell;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr::NonNull;
+
+/// The halves of a channel over the buffer given to [`channel_in`].
+pub type ScopedSender<'a, T> = Sender<'a, T, Remainder>;
+pub type ScopedReceiver<'a, T> = Receiver<'a, T, Remainder>;
+
+/// A bounded channel which lives in a `static`, without any allocation.
+///
+/// The channel is split once into an [`ExactSender`] and an [`ExactReceiver`], which have the
+/// same methods as the halves of [`exact_channel`]. It holds `N` items.
+///
+/// The items left in the channel are never dropped, since the channel itself never is.
+///
+/// ```
+/// use spsc_rs::StaticChannel;
+///
+/// static CHANNEL: StaticChannel<u32, 8> = StaticChannel::new();
+///
+/// let (mut tx, mut rx) = CHANNEL.split().unwrap();
+/// assert!(CHANNEL.split().is_none());
+///
+/// tx.try_send(1).unwrap();
+/// assert_eq!(rx.try_recv().unwrap(), 1);
+/// ```
+///
+/// [`exact_channel`]: crate::exact_channel
+pub struct StaticChannel<T, const N: usize> {
+    buf: UnsafeCell<MaybeUninit<[T; N]>>,
+    /// Initialized by the first `split`, since the ring needs the address of `buf`.
+    shared: UnsafeCell<MaybeUninit<Shared<T, Remainder, AsyncWaker, AtomicPosition>>>,
+    split: AtomicBool,
+}
+
+// Safety: the slots and the shared state are only accessed through the halves, which are
+// handed out once.
+unsafe impl<T: Send, const N: usize> Sync for StaticChannel<T, N> {}
+
+impl<T, const N: usize> StaticChannel<T, N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: UnsafeCell::new(MaybeUninit::uninit()),
+            shared: UnsafeCell::new(MaybeUninit::uninit()),
+            split: AtomicBool::new(false),
+        }
+    }
+
+    /// Returns the two halves of the channel, or `None` if it was already split.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `N` is 0.
+    pub fn split(&'static self) -> Option<(ExactSender<T>, ExactReceiver<T>)> {
+        assert!(N > 0, "the channel needs at least 1 slot");
+
+        if self.split.swap(true, Ordering::AcqRel) {
+            return None;
+        }
+
+        // Safety: `self` is never dropped nor moved, and the flag above makes sure the slots
+        // and the shared state are only initialized once.
+        let shared = unsafe {
+            let slots = NonNull::new_unchecked(self.buf.get()).cast();
+            let ring = ExactRing::from_slots(slots, N);
+            (*self.shared.get()).write(Shared::new(ring, AsyncWaker::default()))
+        };
+        Some(from_shared(SharedRef::borrowed(shared)))
+    }
+}
+
+impl<T, const N: usize> Default for StaticChannel<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A bounded channel over a buffer given to [`channel_in`].
+pub struct ChannelIn<'a, T> {
+    shared: Shared<T, Remainder, AsyncWaker, AtomicPosition>,
+    _buf: PhantomData<&'a mut [MaybeUninit<T>]>,
+}
+
+/// Creates a bounded channel whose ring is `buf`, without any allocation.
+///
+/// The returned [`ChannelIn`] owns the state of the channel, and [`ChannelIn::split`] gives
+/// out the halves, which borrow it. It holds `buf.len()` items, and the items left in the
+/// channel are dropped with it.
+///
+/// ```
+/// use core::mem::MaybeUninit;
+///
+/// let mut buf = [const { MaybeUninit::uninit() }; 16];
+/// let mut channel = spsc_rs::channel_in::<String>(&mut buf);
+/// let (mut tx, mut rx) = channel.split();
+///
+/// std::thread::scope(|s| {
+///     s.spawn(move || tx.blocking_send("hello".to_string()).unwrap());
+///     assert_eq!(rx.blocking_recv().as_deref(), Some("hello"));
+/// });
+/// ```
+///
+/// # Panics
+///
+/// Panics if `buf` is empty.
+pub fn channel_in<T>(buf: &mut [MaybeUninit<T>]) -> ChannelIn<'_, T> {
+    assert!(!buf.is_empty(), "the channel needs at least 1 slot");
+
+    let cap = buf.len();
+    // Safety: the buffer stays borrowed by `ChannelIn`, which owns the ring.
+    let ring = unsafe { ExactRing::from_slots(NonNull::from(buf).cast(), cap) };
+    ChannelIn {
+        shared: Shared::new(ring, AsyncWaker::default()),
+        _buf: PhantomData,
+    }
+}
+
+impl<T> ChannelIn<'_, T> {
+    /// Returns the two halves of the channel.
+    ///
+    /// Once both halves are dropped, the channel can be split again, and the new receiver
+    /// gets the items which were left in it.
+    pub fn split(&mut self) -> (ScopedSender<'_, T>, ScopedReceiver<'_, T>) {
+        // no half of an earlier split is alive, since they borrowed `self`, and they
+        // committed when they were closed.
+        unsafe { self.shared.ring.reset_sides() };
+        self.shared.set_closed(false);
+        from_shared(SharedRef::borrowed(&self.shared))
+    }
+}
@@ -0,0 +1,140 @@
+use crate::bounded::pipe::{self, PipeReader, PipeWriter};
+use crate::bounded::ring::{p2_capacity, And, P2Ring, Ring, Storage};
+use crate::bounded::{from_ring, FullPolicy, P2Receiver, P2Sender};
+use crate::wait::AsyncWaker;
+use std::io;
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::ptr;
+
+/// Creates a bounded channel whose ring is mapped twice back to back in memory, so the
+/// readable and writable regions are always contiguous.
+///
+/// The channel has the same API as [`channel`], but the second slice returned by
+/// [`Receiver::read_slices`] and [`WriteSlots::as_mut_slices`] is always empty. Because the
+/// mapping is made of whole pages, the capacity may be much bigger than `size`.
+///
+/// Only available on Linux with the `mirror` feature, and only for `Copy` items.
+///
+/// [`channel`]: crate::channel
+/// [`WriteSlots::as_mut_slices`]: crate::WriteSlots::as_mut_slices
+pub fn mirrored_channel<T: Copy>(size: usize) -> io::Result<(P2Sender<T>, P2Receiver<T>)> {
+    let ring = P2Ring::mirrored(size)?;
+    Ok(from_ring(ring, AsyncWaker::default()))
+}
+
+/// The same as [`pipe`], but built on a [`mirrored_channel`], so `poll_fill_buf`
+/// always returns all the buffered bytes.
+///
+/// [`pipe`]: crate::pipe
+pub fn mirrored_pipe(size: usize) -> io::Result<(PipeWriter, PipeReader)> {
+    mirrored_channel(size).map(pipe::from_channel)
+}
+
+impl<T: Copy> Ring<T, And> {
+    fn mirrored(cap: usize) -> io::Result<Self> {
+        assert!(
+            mem::size_of::<T>() > 0,
+            "zero sized items can't be mirrored"
+        );
+
+        let page = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as usize;
+        let mut cap = p2_capacity(cap);
+        // The page size is a power of two, so doubling always ends up at whole pages.
+        while !(cap * mem::size_of::<T>()).is_multiple_of(page) {
+            cap = cap.checked_mul(2).expect("capacity overflow");
+        }
+
+        let buf = MirroredBuf::new(cap)?;
+        Ok(Self::from_storage(
+            Storage::Mirrored(buf),
+            And::new(cap),
+            FullPolicy::Wait,
+        ))
+    }
+}
+
+/// `cap` items backed by a memfd, which is mapped twice so the items can be accessed
+/// past the end of the buffer as if they wrapped around.
+pub(crate) struct MirroredBuf<T> {
+    ptr: *mut MaybeUninit<T>,
+    cap: usize,
+    _marker: PhantomData<T>,
+}
+
+impl<T> MirroredBuf<T> {
+    fn new(cap: usize) -> io::Result<Self> {
+        let len = cap * mem::size_of::<T>();
+
+        unsafe {
+            let fd = libc::memfd_create(c"spsc-rs".as_ptr(), libc::MFD_CLOEXEC);
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let res = Self::map(fd, len);
+            // The mappings keep the memory alive.
+            libc::close(fd);
+
+            res.map(|ptr| Self {
+                ptr: ptr.cast(),
+                cap,
+                _marker: PhantomData,
+            })
+        }
+    }
+
+    unsafe fn map(fd: libc::c_int, len: usize) -> io::Result<*mut libc::c_void> {
+        if libc::ftruncate(fd, len as libc::off_t) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Reserve the address range first, then map the memfd twice over it.
+        let base = libc::mmap(
+            ptr::null_mut(),
+            2 * len,
+            libc::PROT_NONE,
+            libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        );
+        if base == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        for offset in [0, len] {
+            let addr = libc::mmap(
+                base.cast::<u8>().add(offset).cast(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_FIXED,
+                fd,
+                0,
+            );
+            if addr == libc::MAP_FAILED {
+                let err = io::Error::last_os_error();
+                libc::munmap(base, 2 * len);
+                return Err(err);
+            }
+        }
+
+        Ok(base)
+    }
+
+    /// The start of the mapping, which stays valid for `2 * cap` items.
+    pub(crate) fn as_ptr(&self) -> *mut MaybeUninit<T> {
+        self.ptr
+    }
+
+    pub(crate) fn cap(&self) -> usize {
+        self.cap
+    }
+}
+
+impl<T> Drop for MirroredBuf<T> {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr.cast(), 2 * self.cap * mem::size_of::<T>());
+        }
+    }
+}
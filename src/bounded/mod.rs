@@ -1,110 +1,580 @@
-mod ring;
+mod bip;
+#[cfg(not(loom))]
+mod borrowed;
+#[cfg(all(target_os = "linux", feature = "mirror", not(loom)))]
+mod mirror;
+#[cfg(feature = "std")]
+mod pipe;
+pub(crate) mod ring;
+mod slots;
 pub mod wrapper;
 
-use crate::atomic_waker::AtomicWaker;
+pub use self::bip::{bip_channel, BipReceiver, BipSender, RecordGuard, RecordSlot};
+#[cfg(not(loom))]
+pub use self::borrowed::{channel_in, ChannelIn, ScopedReceiver, ScopedSender, StaticChannel};
+#[cfg(all(target_os = "linux", feature = "mirror", not(loom)))]
+pub use self::mirror::{mirrored_channel, mirrored_pipe};
+#[cfg(feature = "std")]
+pub use self::pipe::{pipe, PipeReader, PipeWriter};
+pub use self::slots::{Permit, RecvGuard, WriteSlots};
+
+#[cfg(not(loom))]
+use crate::allocator::{AllocError, Allocator};
+#[cfg(feature = "std")]
+use crate::blocking;
 use crate::bounded::ring::{And, ExactRing, Indexer, P2Ring, Remainder, Ring};
+use crate::cache_padded::CachePadded;
 use crate::error::TryRecvError;
 use crate::error::{SendError, TrySendError};
-use crate::loom::{Arc, AtomicBool, Ordering};
+use crate::loom::{AtomicPosition, Counter, Ordering};
+#[cfg(feature = "std")]
+use crate::notify;
+use crate::wait::{AsyncWaker, WaitStrategy};
+#[cfg(not(loom))]
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+use core::ops::Deref;
+use core::pin::Pin;
+use core::ptr::NonNull;
+use core::task::{Context, Poll, Waker};
+#[cfg(feature = "std")]
+use core::time::Duration;
+use core::{cmp, ptr};
 use futures_util::future::poll_fn;
 use futures_util::Stream;
-use std::pin::Pin;
-use std::task::{Context, Poll};
-
-struct Shared<T, I: Indexer> {
-    ring: Ring<T, I>,
-    consumer: AtomicWaker,
-    producer: AtomicWaker,
-    closed: AtomicBool,
+
+/// The wakers and the flag are padded, so waking one side or closing the channel doesn't
+/// invalidate the cache lines of the ring positions.
+///
+/// The positions and the flag are atomics, except in the [`local`] channels, whose `C` is a
+/// `Cell`.
+///
+/// [`local`]: crate::local
+struct Shared<T, I: Indexer, W: WaitStrategy, C: Counter> {
+    ring: Ring<T, I, C>,
+    consumer: CachePadded<W>,
+    producer: CachePadded<W>,
+    /// Non-zero once either half closed the channel.
+    closed: CachePadded<C>,
 }
 
-impl<T, I: Indexer> Shared<T, I> {
-    fn new(ring: Ring<T, I>) -> Self {
+impl<T, I: Indexer, W: WaitStrategy + Clone, C: Counter> Shared<T, I, W, C> {
+    fn new(ring: Ring<T, I, C>, wait: W) -> Self {
         Self {
             ring,
-            consumer: Default::default(),
-            producer: Default::default(),
+            consumer: CachePadded::new(wait.clone()),
+            producer: CachePadded::new(wait),
             closed: Default::default(),
         }
     }
 }
 
-pub type P2Sender<T> = Sender<T, And>;
-pub type P2Receiver<T> = Receiver<T, And>;
+impl<T, I: Indexer, W: WaitStrategy, C: Counter> Shared<T, I, W, C> {
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire) != 0
+    }
+
+    fn set_closed(&self, closed: bool) {
+        self.closed.store(closed as usize, Ordering::Release);
+    }
+
+    /// Finds the slot for the next item without waiting.
+    ///
+    /// Returns `None` if the channel is full and the policy doesn't discard items,
+    /// or `Some(None)` if the next item should be discarded. The same callers as `write_slot`.
+    fn next_slot(&self) -> Option<Option<usize>> {
+        if let Some(idx) = self.ring.next_idx() {
+            return Some(Some(idx));
+        }
+
+        // Publish the staged items once the channel is full, so the receiver can make room,
+        // and the evicted head is never an item it can't see yet.
+        self.commit_write();
+        match self.ring.policy() {
+            FullPolicy::Wait | FullPolicy::Error => None,
+            FullPolicy::DropNewest => Some(None),
+            FullPolicy::DropOldest => Some(self.ring.evict_oldest()),
+        }
+    }
+
+    /// Writes the item into the slot returned by `next_slot`, `None` means discarding it.
+    /// The item is staged, it is only published with the next commit.
+    ///
+    /// Only the sender, or a guard holding its mutable reference, may call it.
+    fn write_slot(&self, item: T, slot: Option<usize>) {
+        match slot {
+            Some(idx) => unsafe {
+                self.ring.stage_unchecked(item, idx);
+            },
+            None => {
+                self.ring.reject();
+                drop(item);
+            }
+        }
+    }
+
+    fn send_at(&self, item: T, slot: Option<usize>) {
+        self.write_slot(item, slot);
+        self.commit_write();
+    }
+
+    /// Publishes the staged items, and wakes the receiver if there were any. The same
+    /// callers as `write_slot`.
+    fn commit_write(&self) {
+        if unsafe { self.ring.commit_write() } {
+            self.consumer.wake_by_ref();
+        }
+    }
+
+    /// Releases the popped items, and wakes the sender if there were any.
+    /// Only the receiver may call it.
+    fn commit_read(&self) {
+        if unsafe { self.ring.commit_read() } {
+            self.producer.wake_by_ref();
+        }
+    }
+
+    /// Wakes the sender after the receiver popped some items, unless they are only released
+    /// by the next commit in the batch receive mode. The same callers as `commit_read`.
+    fn popped(&self) {
+        if !self.ring.defers_read() {
+            self.producer.wake_by_ref();
+        }
+    }
+
+    /// The body of [`Receiver::consume`], for the receiver or a guard holding its
+    /// mutable reference.
+    fn consume(&self, n: usize) {
+        assert!(
+            n <= self.ring.readable_len(),
+            "consume more items than readable"
+        );
+
+        if n > 0 {
+            unsafe {
+                self.ring.consume(n);
+            }
+            self.popped();
+        }
+    }
+}
+
+/// How a half holds the shared state: owned together with the other half, or borrowed
+/// from a [`StaticChannel`] or a [`ChannelIn`].
+///
+/// The borrow is a pointer rather than a reference, so `'static` halves of the owned
+/// channels don't require `T: 'static`.
+enum SharedRef<'a, T, I: Indexer, W: WaitStrategy, C: Counter> {
+    /// An `Arc`, or an `Rc` in the `local` channels.
+    Owned(C::Ptr<Shared<T, I, W, C>>),
+    #[cfg_attr(loom, allow(dead_code))]
+    Borrowed(NonNull<Shared<T, I, W, C>>, PhantomData<&'a ()>),
+}
+
+// Safety: the same as `Arc<Shared>`, both halves access the shared state concurrently.
+// With `Cell` positions, `Shared` is neither `Send` nor `Sync`, the same as `Rc<Shared>`.
+unsafe impl<T, I: Indexer, W: WaitStrategy, C: Counter> Send for SharedRef<'_, T, I, W, C> where
+    Shared<T, I, W, C>: Send + Sync
+{
+}
+
+unsafe impl<T, I: Indexer, W: WaitStrategy, C: Counter> Sync for SharedRef<'_, T, I, W, C> where
+    Shared<T, I, W, C>: Send + Sync
+{
+}
+
+#[cfg(not(loom))]
+impl<'a, T, I: Indexer, W: WaitStrategy, C: Counter> SharedRef<'a, T, I, W, C> {
+    fn borrowed(shared: &'a Shared<T, I, W, C>) -> Self {
+        SharedRef::Borrowed(NonNull::from(shared), PhantomData)
+    }
+}
+
+impl<T, I: Indexer, W: WaitStrategy, C: Counter> Clone for SharedRef<'_, T, I, W, C> {
+    fn clone(&self) -> Self {
+        match self {
+            SharedRef::Owned(shared) => SharedRef::Owned(shared.clone()),
+            SharedRef::Borrowed(shared, _) => SharedRef::Borrowed(*shared, PhantomData),
+        }
+    }
+}
+
+impl<T, I: Indexer, W: WaitStrategy, C: Counter> Deref for SharedRef<'_, T, I, W, C> {
+    type Target = Shared<T, I, W, C>;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            SharedRef::Owned(shared) => shared,
+            // Safety: the owner of the shared state outlives `'a`.
+            SharedRef::Borrowed(shared, _) => unsafe { shared.as_ref() },
+        }
+    }
+}
+
+/// What the [`Sender`] does with a new item when the bounded channel is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FullPolicy {
+    /// Wait for the receiver to make room, this is what [`channel`] and [`exact_channel`] do.
+    #[default]
+    Wait,
+    /// Discard the new item, the items in the channel are kept.
+    DropNewest,
+    /// Discard the oldest item in the channel to make room for the new one, so the receiver
+    /// always sees the freshest items.
+    ///
+    /// The items borrowed by the receiver through [`Receiver::peek`], [`Receiver::read_slices`]
+    /// or [`Receiver::recv_ref`] can't be discarded, the new item is discarded instead until
+    /// the receiver pops again.
+    DropOldest,
+    /// Fail with `SendError::Full` immediately, the sending methods never wait.
+    Error,
+}
+
+pub type P2Sender<T, W = AsyncWaker> = Sender<'static, T, And, W>;
+pub type P2Receiver<T, W = AsyncWaker> = Receiver<'static, T, And, W>;
 
 pub fn channel<T>(size: usize) -> (P2Sender<T>, P2Receiver<T>) {
-    let ring = P2Ring::with_capacity(size);
-    let shared = Arc::new(Shared::new(ring));
-    (
-        Sender {
-            inner: shared.clone(),
-        },
-        Receiver { inner: shared },
-    )
+    channel_with_policy(size, FullPolicy::Wait)
+}
+
+/// Creates a bounded channel which handles a full channel according to `policy`.
+///
+/// Under the drop policies, the sender never waits and the discarded items are counted in
+/// [`Sender::discarded`]. The receiver can use [`Receiver::recv_seq`] to find out where
+/// the items are missing.
+pub fn channel_with_policy<T>(size: usize, policy: FullPolicy) -> (P2Sender<T>, P2Receiver<T>) {
+    from_ring(P2Ring::with_capacity(size, policy), AsyncWaker::default())
 }
 
-pub type ExactSender<T> = Sender<T, Remainder>;
-pub type ExactReceiver<T> = Receiver<T, Remainder>;
+/// Creates a bounded channel whose sides wait for each other with `wait`, see [`WaitStrategy`].
+pub fn channel_with_wait<T, W>(size: usize, wait: W) -> (P2Sender<T, W>, P2Receiver<T, W>)
+where
+    W: WaitStrategy + Clone,
+{
+    from_ring(P2Ring::with_capacity(size, FullPolicy::Wait), wait)
+}
+
+/// Creates a bounded channel whose ring is allocated by `alloc`, see [`allocator`].
+///
+/// [`allocator`]: crate::allocator
+#[cfg(not(loom))]
+pub fn channel_with_alloc<T, A: Allocator>(
+    size: usize,
+    alloc: A,
+) -> Result<(P2Sender<T>, P2Receiver<T>), AllocError> {
+    let ring = P2Ring::with_capacity_in(size, FullPolicy::Wait, Box::new(alloc))?;
+    Ok(from_ring(ring, AsyncWaker::default()))
+}
+
+pub type ExactSender<T, W = AsyncWaker> = Sender<'static, T, Remainder, W>;
+pub type ExactReceiver<T, W = AsyncWaker> = Receiver<'static, T, Remainder, W>;
 
 pub fn exact_channel<T>(size: usize) -> (ExactSender<T>, ExactReceiver<T>) {
-    let ring = ExactRing::with_capacity(size);
-    let shared = Arc::new(Shared::new(ring));
+    exact_channel_with_policy(size, FullPolicy::Wait)
+}
+
+/// The same as [`channel_with_policy`], but with the exact size of [`exact_channel`].
+pub fn exact_channel_with_policy<T>(
+    size: usize,
+    policy: FullPolicy,
+) -> (ExactSender<T>, ExactReceiver<T>) {
+    from_ring(
+        ExactRing::with_capacity(size, policy),
+        AsyncWaker::default(),
+    )
+}
+
+/// The same as [`channel_with_wait`], but with the exact size of [`exact_channel`].
+pub fn exact_channel_with_wait<T, W>(
+    size: usize,
+    wait: W,
+) -> (ExactSender<T, W>, ExactReceiver<T, W>)
+where
+    W: WaitStrategy + Clone,
+{
+    from_ring(ExactRing::with_capacity(size, FullPolicy::Wait), wait)
+}
+
+/// The same as [`channel_with_alloc`], but with the exact size of [`exact_channel`].
+#[cfg(not(loom))]
+pub fn exact_channel_with_alloc<T, A: Allocator>(
+    size: usize,
+    alloc: A,
+) -> Result<(ExactSender<T>, ExactReceiver<T>), AllocError> {
+    let ring = ExactRing::with_capacity_in(size, FullPolicy::Wait, Box::new(alloc))?;
+    Ok(from_ring(ring, AsyncWaker::default()))
+}
+
+/// The `park` of the `side` strategy for the blocking helpers. It holds its own clone of the
+/// shared state instead of a borrow of the handle, so the poll closure can still borrow the
+/// handle mutably.
+#[cfg(feature = "std")]
+fn parker<'a, T, I: Indexer, W: WaitStrategy, C: Counter>(
+    shared: &SharedRef<'a, T, I, W, C>,
+    side: fn(&Shared<T, I, W, C>) -> &W,
+) -> impl Fn(Option<Duration>) + use<'a, T, I, W, C> {
+    let shared = shared.clone();
+    move |timeout| side(&shared).park(timeout)
+}
+
+/// The two halves of a channel, as returned by `from_ring`.
+type Halves<'a, T, I, W, C> = (Sender<'a, T, I, W, C>, Receiver<'a, T, I, W, C>);
+
+pub(crate) fn from_ring<T, I, W, C>(ring: Ring<T, I, C>, wait: W) -> Halves<'static, T, I, W, C>
+where
+    I: Indexer,
+    W: WaitStrategy + Clone,
+    C: Counter,
+{
+    from_shared(SharedRef::Owned(C::share(Shared::new(ring, wait))))
+}
+
+fn from_shared<T, I, W, C>(shared: SharedRef<'_, T, I, W, C>) -> Halves<'_, T, I, W, C>
+where
+    I: Indexer,
+    W: WaitStrategy,
+    C: Counter,
+{
     (
         Sender {
             inner: shared.clone(),
+            notify: None,
+            batch: false,
+        },
+        Receiver {
+            inner: shared,
+            notify: None,
         },
-        Receiver { inner: shared },
     )
 }
 
-pub struct Sender<T, I: Indexer> {
-    inner: Arc<Shared<T, I>>,
+/// The sending half of a bounded channel.
+///
+/// The lifetime is `'static` unless the half borrows the buffer given to [`channel_in`].
+pub struct Sender<'a, T, I: Indexer, W: WaitStrategy = AsyncWaker, C: Counter = AtomicPosition> {
+    inner: SharedRef<'a, T, I, W, C>,
+    /// The callback given to `set_notify`.
+    notify: Option<Waker>,
+    /// Whether `start_send` leaves the items staged, see `set_batch_send`.
+    batch: bool,
 }
 
-impl<T, I: Indexer> Drop for Sender<T, I> {
+impl<T, I: Indexer, W: WaitStrategy, C: Counter> Drop for Sender<'_, T, I, W, C> {
     fn drop(&mut self) {
-        // we need to wake up the receiver before
-        // the sender was totally dropped, otherwise the receiver may hang up.
-        self.inner.closed.store(true, Ordering::Release);
-        self.inner.consumer.wake_by_ref();
+        self.close();
     }
 }
 
-impl<T, I: Indexer> Sender<T, I> {
-    pub fn start_send(&mut self, item: T) -> Result<(), SendError> {
+impl<T, I: Indexer, W: WaitStrategy, C: Counter> Sender<'_, T, I, W, C> {
+    /// Puts an item into the channel without notifying the receiver, use [`flush`] to notify
+    /// it once a batch of items is sent. With [`set_batch_send`], the item isn't even
+    /// published until then.
+    ///
+    /// If the channel is closed, or full under the `Wait` and `Error` policies, the item is
+    /// returned back in the error.
+    ///
+    /// [`flush`]: Sender::flush
+    /// [`set_batch_send`]: Sender::set_batch_send
+    pub fn start_send(&mut self, item: T) -> Result<(), TrySendError<T>> {
+        self.stage(item)?;
+        if !self.batch {
+            // publish it right away, only `flush` notifies the receiver.
+            unsafe { self.inner.ring.commit_write() };
+        }
+        Ok(())
+    }
+
+    /// Sends an item without waiting, and notifies the receiver.
+    ///
+    /// If the channel is full or closed, the item is returned back in the error.
+    pub fn try_send(&mut self, item: T) -> Result<(), TrySendError<T>> {
+        self.stage(item)?;
+        self.inner.commit_write();
+        Ok(())
+    }
+
+    /// Writes the item into the next slot without publishing it.
+    fn stage(&mut self, item: T) -> Result<(), TrySendError<T>> {
         if self.is_closed() {
-            return Err(SendError::Disconnected);
+            return Err(TrySendError {
+                err: SendError::Disconnected,
+                val: item,
+            });
         }
 
-        if let Some(idx) = self.inner.ring.next_idx() {
-            unsafe {
-                self.inner.ring.set_unchecked(item, idx);
+        match self.inner.next_slot() {
+            Some(slot) => {
+                self.inner.write_slot(item, slot);
+                Ok(())
             }
-            Ok(())
-        } else {
+            None => {
+                self.arm_notify();
+                Err(TrySendError {
+                    err: SendError::Full,
+                    val: item,
+                })
+            }
+        }
+    }
+
+    /// Makes [`start_send`] only stage the items, so a batch of them moves the position
+    /// shared with the receiver only once.
+    ///
+    /// The staged items are published by [`flush`] or [`commit`], when the channel is found
+    /// full by `start_send` or [`poll_ready`], and by any method which sends right away.
+    /// Turning the batch mode off publishes them too.
+    ///
+    /// [`start_send`]: Sender::start_send
+    /// [`flush`]: Sender::flush
+    /// [`commit`]: Sender::commit
+    /// [`poll_ready`]: Sender::poll_ready
+    pub fn set_batch_send(&mut self, batch: bool) {
+        self.batch = batch;
+        if !batch {
+            self.inner.commit_write();
+        }
+    }
+
+    /// Publishes the items staged by [`start_send`], and notifies the receiver, see
+    /// [`set_batch_send`].
+    ///
+    /// Unlike [`flush`], this doesn't wait for the receiver to take them.
+    ///
+    /// [`start_send`]: Sender::start_send
+    /// [`set_batch_send`]: Sender::set_batch_send
+    /// [`flush`]: Sender::flush
+    pub fn commit(&mut self) {
+        self.inner.commit_write();
+    }
+
+    /// Reserves up to `n` free slots, so items can be written in place and published together.
+    ///
+    /// The returned [`WriteSlots`] may hold fewer than `n` slots if the channel doesn't have
+    /// enough room. If the channel is full, `Err(SendError::Full)` is returned whatever the
+    /// [`FullPolicy`] is.
+    pub fn reserve_slots(&mut self, n: usize) -> Result<WriteSlots<'_, T, I, W, C>, SendError> {
+        if self.is_closed() {
+            return Err(SendError::Disconnected);
+        }
+
+        let vacant = self.inner.ring.vacant_len();
+        if vacant == 0 {
             Err(SendError::Full)
+        } else {
+            Ok(WriteSlots::new(self, cmp::min(n, vacant)))
+        }
+    }
+
+    /// Copies as many items as the free space allows into the channel, and publishes them
+    /// to the receiver at once.
+    ///
+    /// Returns the number of items sent, or `Err(SendError::Full)` if there is no free space.
+    /// Like [`reserve_slots`], this never discards items.
+    ///
+    /// [`reserve_slots`]: Sender::reserve_slots
+    pub fn send_slice(&mut self, items: &[T]) -> Result<usize, SendError>
+    where
+        T: Copy,
+    {
+        let mut slots = self.reserve_slots(items.len())?;
+        let n = slots.len();
+        let (left, right) = slots.as_mut_slices();
+        let (first, second) = items[..n].split_at(left.len());
+        unsafe {
+            ptr::copy_nonoverlapping(first.as_ptr(), left.as_mut_ptr().cast(), first.len());
+            ptr::copy_nonoverlapping(second.as_ptr(), right.as_mut_ptr().cast(), second.len());
+            slots.assume_written(n);
+        }
+        slots.commit(n);
+        Ok(n)
+    }
+
+    /// Sends all the items, waiting for free space as needed.
+    ///
+    /// The free space is filled before the items are published to the receiver, so there is
+    /// only one wake up for each batch. If the receiver is gone, the item that failed to be sent
+    /// is returned, and the rest of `items` are dropped.
+    pub async fn send_iter<It>(&mut self, items: It) -> Result<(), TrySendError<T>>
+    where
+        It: IntoIterator<Item = T>,
+    {
+        let mut items = items.into_iter();
+        let mut next = items.next();
+
+        while let Some(item) = next {
+            match poll_fn(|cx| self.poll_next_pos(cx)).await {
+                Ok(Some(_)) => {}
+                Ok(None) => {
+                    self.inner.ring.reject();
+                    next = items.next();
+                    continue;
+                }
+                Err(err) => return Err(TrySendError { err, val: item }),
+            }
+
+            let mut slots = match self.reserve_slots(usize::MAX) {
+                Ok(slots) => slots,
+                Err(err) => return Err(TrySendError { err, val: item }),
+            };
+
+            next = Some(item);
+            while slots.written() < slots.len() {
+                match next.take() {
+                    Some(item) => {
+                        // there are still free slots, so this can't fail.
+                        let _ = slots.push(item);
+                        next = items.next();
+                    }
+                    None => break,
+                }
+            }
+
+            let written = slots.written();
+            slots.commit(written);
         }
+
+        Ok(())
     }
 
     pub fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), SendError>> {
-        if self.inner.ring.is_full() {
-            self.poll_flush(cx)
-        } else {
-            Poll::Ready(Ok(()))
+        // the same check as `start_send`, which only loads the consumer position once the
+        // cached one says the channel is full.
+        if self.inner.ring.next_idx().is_some() {
+            return Poll::Ready(Ok(()));
+        }
+
+        // the receiver has to see the staged items to make room.
+        self.inner.commit_write();
+        match self.inner.ring.policy() {
+            FullPolicy::Wait => self.poll_flush(cx),
+            FullPolicy::Error => Poll::Ready(Err(SendError::Full)),
+            // the next item will be sent anyway.
+            FullPolicy::DropNewest | FullPolicy::DropOldest => Poll::Ready(Ok(())),
         }
     }
 
     pub fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), SendError>> {
+        self.inner.commit_write();
         if self.is_closed() {
             Poll::Ready(Err(SendError::Disconnected))
-        } else if self.inner.ring.is_empty() {
+        } else if self.inner.ring.is_drained() {
             // if the inner bounded is already empty,
             // we just return ok to avoid some atomic operation.
             Poll::Ready(Ok(()))
         } else {
             self.inner.producer.register(cx.waker());
             self.inner.consumer.wake_by_ref();
-            Poll::Pending
+
+            // The same as `poll_next_pos`, the receiver could take all the items or close
+            // the channel before the register operation, so we need to check again.
+            if self.is_closed() {
+                Poll::Ready(Err(SendError::Disconnected))
+            } else if self.inner.ring.is_drained() {
+                Poll::Ready(Ok(()))
+            } else {
+                Poll::Pending
+            }
         }
     }
 
@@ -113,39 +583,122 @@ impl<T, I: Indexer> Sender<T, I> {
     }
 
     pub async fn send(&mut self, item: T) -> Result<(), TrySendError<T>> {
-        let idx = match poll_fn(|cx| self.poll_next_pos(cx)).await {
-            Ok(idx) => idx,
-            Err(err) => return Err(TrySendError { err, val: item }),
-        };
-
-        unsafe {
-            self.inner.ring.set_unchecked(item, idx);
+        match poll_fn(|cx| self.poll_next_pos(cx)).await {
+            Ok(slot) => {
+                self.inner.send_at(item, slot);
+                Ok(())
+            }
+            Err(err) => Err(TrySendError { err, val: item }),
         }
+    }
 
-        self.inner.consumer.wake_by_ref();
+    /// Waits for a free slot, and reserves it for the next item.
+    ///
+    /// Unlike [`send`], the item doesn't need to be built before there is room, and
+    /// sending through the returned [`Permit`] can't fail.
+    ///
+    /// Under the drop policies, the permit is returned right away, and the policy is applied
+    /// when it is reserved: if the channel is full, `DropNewest` makes the permit discard the
+    /// item, and `DropOldest` evicts the head item right away, so the evicted item is lost
+    /// even if the permit is dropped without sending.
+    ///
+    /// [`send`]: Sender::send
+    pub async fn reserve(&mut self) -> Result<Permit<'_, T, I, W, C>, SendError> {
+        let slot = poll_fn(|cx| self.poll_next_pos(cx)).await?;
+        Ok(Permit::new(self, slot))
+    }
 
-        Ok(())
+    /// The poll version of [`reserve`], for hand-written futures.
+    ///
+    /// [`reserve`]: Sender::reserve
+    pub fn poll_reserve(
+        &mut self,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Permit<'_, T, I, W, C>, SendError>> {
+        match self.poll_next_pos(cx) {
+            Poll::Ready(Ok(slot)) => Poll::Ready(Ok(Permit::new(self, slot))),
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Calls `notify` when the channel has room again, or is closed, for schedulers which
+    /// are neither async runtimes nor threads.
+    ///
+    /// The callback is registered where the waker of a sending task would be, and fires once
+    /// per registration. It is registered right away, and again each time [`start_send`] or
+    /// [`try_send`] finds the channel full, so the scheduler retries until the channel is full
+    /// again. The callback may fire on the receiver's thread, or right away on this thread if
+    /// the channel already has room, so it should only schedule the work.
+    ///
+    /// An async method polled afterwards registers its own task instead, until the channel
+    /// is found full again.
+    ///
+    /// [`start_send`]: Sender::start_send
+    /// [`try_send`]: Sender::try_send
+    #[cfg(feature = "std")]
+    pub fn set_notify(&mut self, notify: std::sync::Arc<dyn Fn() + Send + Sync>) {
+        self.notify = Some(notify::waker(notify));
+        self.arm_notify();
     }
 
     /// Returns whether this channel is closed.
     pub fn is_closed(&self) -> bool {
-        self.inner.closed.load(Ordering::Acquire)
+        self.inner.is_closed()
+    }
+
+    /// Registers the `set_notify` callback, then checks again like `poll_next_pos` does.
+    fn arm_notify(&self) {
+        if let Some(notify) = &self.notify {
+            self.inner.producer.register(notify);
+            if !self.inner.ring.is_full() || self.is_closed() {
+                notify.wake_by_ref();
+            }
+        }
+    }
+
+    /// The number of items the channel holds. [`channel`] rounds the size up to a power
+    /// of two, while [`exact_channel`] holds exactly the size it was given.
+    ///
+    /// [`channel`]: crate::channel
+    /// [`exact_channel`]: crate::exact_channel
+    pub fn capacity(&self) -> usize {
+        self.inner.ring.capacity()
+    }
+
+    /// The number of items discarded because the channel was full, see [`FullPolicy`].
+    pub fn discarded(&self) -> usize {
+        self.inner.ring.discarded()
     }
 
-    fn poll_next_pos(&mut self, cx: &mut Context<'_>) -> Poll<Result<usize, SendError>> {
+    pub(crate) fn close(&mut self) {
+        // the staged items must be visible before the flag, the receiver only checks
+        // the ring once more after seeing it.
+        unsafe { self.inner.ring.commit_write() };
+        // we need to wake up the receiver before
+        // the sender was totally dropped, otherwise the receiver may hang up.
+        self.inner.set_closed(true);
+        self.inner.consumer.wake_by_ref();
+    }
+
+    fn poll_next_pos(&mut self, cx: &mut Context<'_>) -> Poll<Result<Option<usize>, SendError>> {
         if self.is_closed() {
             return Poll::Ready(Err(SendError::Disconnected));
         }
 
-        if let Some(idx) = self.inner.ring.next_idx() {
-            Poll::Ready(Ok(idx))
+        if let Some(slot) = self.inner.next_slot() {
+            Poll::Ready(Ok(slot))
+        } else if self.inner.ring.policy() == FullPolicy::Error {
+            Poll::Ready(Err(SendError::Full))
         } else {
             self.inner.producer.register(cx.waker());
 
-            // We need to poll again, in case of the receiver take some items during
-            // the register and the previous poll
-            if let Some(idx) = self.inner.ring.next_idx() {
-                Poll::Ready(Ok(idx))
+            // We need to poll again, in case of the receiver take some items or close
+            // the channel during the register and the previous poll
+            if self.is_closed() {
+                Poll::Ready(Err(SendError::Disconnected))
+            } else if let Some(idx) = self.inner.ring.next_idx() {
+                Poll::Ready(Ok(Some(idx)))
             } else {
                 Poll::Pending
             }
@@ -153,17 +706,65 @@ impl<T, I: Indexer> Sender<T, I> {
     }
 }
 
-pub struct Receiver<T, I: Indexer> {
-    inner: Arc<Shared<T, I>>,
+// Not for the `local` channels: the other side runs on the same thread, so blocking could
+// only deadlock.
+#[cfg(feature = "std")]
+impl<T, I: Indexer, W: WaitStrategy> Sender<'_, T, I, W> {
+    /// Sends a value, parking the current thread until there is capacity.
+    ///
+    /// This is the synchronous version of [`send`], so the sender can live on a plain thread
+    /// while the receiver is an async task, and no runtime is needed.
+    ///
+    /// [`send`]: Sender::send
+    pub fn blocking_send(&mut self, item: T) -> Result<(), TrySendError<T>> {
+        match blocking::block_on_with(parker(&self.inner, |shared| &shared.producer), |cx| {
+            self.poll_next_pos(cx)
+        }) {
+            Ok(slot) => {
+                self.inner.send_at(item, slot);
+                Ok(())
+            }
+            Err(err) => Err(TrySendError { err, val: item }),
+        }
+    }
+
+    /// Sends a value, parking the current thread for at most `timeout` to wait for capacity.
+    ///
+    /// If the channel is still full when the timeout elapses, `SendError::Full` is returned
+    /// together with the value.
+    pub fn send_timeout(&mut self, item: T, timeout: Duration) -> Result<(), TrySendError<T>> {
+        match blocking::block_on_timeout_with(
+            parker(&self.inner, |shared| &shared.producer),
+            timeout,
+            |cx| self.poll_next_pos(cx),
+        ) {
+            Some(Ok(slot)) => {
+                self.inner.send_at(item, slot);
+                Ok(())
+            }
+            Some(Err(err)) => Err(TrySendError { err, val: item }),
+            None => Err(TrySendError {
+                err: SendError::Full,
+                val: item,
+            }),
+        }
+    }
 }
 
-impl<T, I: Indexer> Drop for Receiver<T, I> {
+/// The receiving half of a bounded channel, see [`Sender`].
+pub struct Receiver<'a, T, I: Indexer, W: WaitStrategy = AsyncWaker, C: Counter = AtomicPosition> {
+    inner: SharedRef<'a, T, I, W, C>,
+    /// The callback given to `set_notify`.
+    notify: Option<Waker>,
+}
+
+impl<T, I: Indexer, W: WaitStrategy, C: Counter> Drop for Receiver<'_, T, I, W, C> {
     fn drop(&mut self) {
         self.close()
     }
 }
 
-impl<T, I: Indexer> Stream for Receiver<T, I> {
+impl<T, I: Indexer, W: WaitStrategy, C: Counter> Stream for Receiver<'_, T, I, W, C> {
     type Item = T;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
@@ -171,9 +772,19 @@ impl<T, I: Indexer> Stream for Receiver<T, I> {
     }
 }
 
-impl<T, I: Indexer> Receiver<T, I> {
+impl<T, I: Indexer, W: WaitStrategy, C: Counter> Receiver<'_, T, I, W, C> {
     pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
-        match self.inner.ring.try_pop() {
+        self.try_recv_seq().map(|(_, item)| item)
+    }
+
+    /// The same as [`try_recv`], but also returns the sequence number of the item.
+    ///
+    /// Sequence numbers count every item given to the sender, including the discarded ones,
+    /// so a gap between two received items means some items were discarded in between.
+    ///
+    /// [`try_recv`]: Receiver::try_recv
+    pub fn try_recv_seq(&mut self) -> Result<(u64, T), TryRecvError> {
+        match self.inner.ring.try_pop_seq() {
             None => {
                 // If there is no item in this bounded, we need to
                 // check closed and try pop again.
@@ -182,11 +793,12 @@ impl<T, I: Indexer> Receiver<T, I> {
                 // receiver try pop first, and sender send an item then close.
                 // If we just check closed without pop again, the remaining item will be lost.
                 if self.is_closed() {
-                    match self.inner.ring.try_pop() {
+                    match self.inner.ring.try_pop_seq() {
                         None => Err(TryRecvError::Disconnected),
                         Some(item) => Ok(item),
                     }
                 } else {
+                    self.arm_notify();
                     Err(TryRecvError::Empty)
                 }
             }
@@ -195,13 +807,16 @@ impl<T, I: Indexer> Receiver<T, I> {
     }
 
     pub fn poll_want_recv(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+        self.inner.commit_read();
         if self.is_closed() {
             return Poll::Ready(());
         }
 
         self.inner.consumer.register(cx.waker());
         self.inner.producer.wake_by_ref();
-        if self.inner.ring.is_empty() {
+
+        // The sender could be closed before the register operation, see `poll_recv`.
+        if self.inner.ring.is_empty() && !self.is_closed() {
             Poll::Pending
         } else {
             Poll::Ready(())
@@ -213,10 +828,30 @@ impl<T, I: Indexer> Receiver<T, I> {
     }
 
     pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.poll_recv_seq(cx)
+            .map(|item| item.map(|(_, item)| item))
+    }
+
+    pub async fn recv(&mut self) -> Option<T> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    /// The same as [`recv`], but also returns the sequence number of the item,
+    /// see [`try_recv_seq`].
+    ///
+    /// [`recv`]: Receiver::recv
+    /// [`try_recv_seq`]: Receiver::try_recv_seq
+    pub async fn recv_seq(&mut self) -> Option<(u64, T)> {
+        poll_fn(|cx| self.poll_recv_seq(cx)).await
+    }
+
+    pub fn poll_recv_seq(&mut self, cx: &mut Context<'_>) -> Poll<Option<(u64, T)>> {
         if let Poll::Ready(op) = self.poll_next_msg() {
             return Poll::Ready(Some(op));
         }
 
+        // the sender may be waiting for the room of a batch.
+        self.inner.commit_read();
         self.inner.consumer.register(cx.waker());
 
         // 1. We need to poll again,
@@ -235,25 +870,267 @@ impl<T, I: Indexer> Receiver<T, I> {
         }
     }
 
-    pub async fn recv(&mut self) -> Option<T> {
-        poll_fn(|cx| self.poll_recv(cx)).await
+    /// Moves up to `limit` items to the end of `buf` without waiting, returns the number
+    /// of received items.
+    ///
+    /// All the items are popped with a single position update, and the sender is notified once.
+    pub fn try_recv_many(&mut self, buf: &mut Vec<T>, limit: usize) -> usize {
+        let n = unsafe { self.inner.ring.pop_into(buf, limit) };
+        if n > 0 {
+            self.inner.popped();
+        }
+        n
+    }
+
+    /// Receives up to `limit` items into `buf`, waiting until there is at least one.
+    ///
+    /// Returns `0` if `limit` is `0`, or once the channel is closed and all the remaining
+    /// values are consumed.
+    pub async fn recv_many(&mut self, buf: &mut Vec<T>, limit: usize) -> usize {
+        poll_fn(|cx| self.poll_recv_many(cx, buf, limit)).await
+    }
+
+    pub fn poll_recv_many(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut Vec<T>,
+        limit: usize,
+    ) -> Poll<usize> {
+        if limit == 0 {
+            return Poll::Ready(0);
+        }
+
+        match self.poll_readable(cx) {
+            Poll::Ready(true) => Poll::Ready(self.try_recv_many(buf, limit)),
+            Poll::Ready(false) => Poll::Ready(0),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Returns the head item without popping it.
+    pub fn peek(&self) -> Option<&T> {
+        self.read_slices().0.first()
+    }
+
+    /// The mutable version of [`peek`].
+    ///
+    /// [`peek`]: Receiver::peek
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        self.read_slices_mut().0.first_mut()
+    }
+
+    /// Pops the head item only if `pred` returns `true` for it.
+    pub fn recv_if(&mut self, pred: impl FnOnce(&T) -> bool) -> Option<T> {
+        match self.peek() {
+            Some(item) if pred(item) => match self.poll_next_msg() {
+                Poll::Ready((_, item)) => Some(item),
+                Poll::Pending => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over the items in the channel, without popping them.
+    ///
+    /// Items sent after this method is called are not visited.
+    pub fn iter_pending(&self) -> impl Iterator<Item = &T> + '_ {
+        let (left, right) = self.read_slices();
+        left.iter().chain(right)
+    }
+
+    /// Returns the head item without moving it out of the channel. The item is popped
+    /// when the returned guard is dropped.
+    ///
+    /// `None` is returned once the channel is closed and all the remaining values are consumed.
+    pub async fn recv_ref(&mut self) -> Option<RecvGuard<'_, T, I, W, C>> {
+        if poll_fn(|cx| self.poll_readable(cx)).await {
+            Some(RecvGuard::new(self))
+        } else {
+            None
+        }
+    }
+
+    /// The non-blocking version of [`recv_ref`].
+    ///
+    /// [`recv_ref`]: Receiver::recv_ref
+    pub fn try_recv_ref(&mut self) -> Result<RecvGuard<'_, T, I, W, C>, TryRecvError> {
+        // The same as `try_recv`, we must check the ring again after seeing the channel closed.
+        if self.inner.ring.readable_len() == 0 {
+            if !self.is_closed() {
+                return Err(TryRecvError::Empty);
+            } else if self.inner.ring.readable_len() == 0 {
+                return Err(TryRecvError::Disconnected);
+            }
+        }
+
+        Ok(RecvGuard::new(self))
+    }
+
+    /// Returns all the readable items in place. The second slice is only non-empty
+    /// when the items wrap around the end of the ring.
+    ///
+    /// Use [`consume`] to pop the items after reading them.
+    ///
+    /// [`consume`]: Receiver::consume
+    pub fn read_slices(&self) -> (&[T], &[T]) {
+        // Safety: we are the only consumer, and the items can neither be popped nor
+        // mutably borrowed while they are borrowed.
+        unsafe { self.inner.ring.readable_slices() }
+    }
+
+    /// The mutable version of [`read_slices`].
+    ///
+    /// [`read_slices`]: Receiver::read_slices
+    pub fn read_slices_mut(&mut self) -> (&mut [T], &mut [T]) {
+        unsafe { self.inner.ring.readable_slices_mut() }
+    }
+
+    /// Drops the first `n` readable items, and notifies the sender once.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than `n` readable items.
+    pub fn consume(&mut self, n: usize) {
+        self.inner.consume(n);
+    }
+
+    /// Only releases the slots of the received items to the sender with [`commit`], or
+    /// [`want_recv`], so a batch of [`try_recv`] calls moves the position shared with the
+    /// sender only once.
+    ///
+    /// The methods which wait for items commit before waiting. Until then the sender doesn't
+    /// see the room of the batch, so commit before going idle in any other way. Turning the
+    /// batch mode off commits too. It has no effect under `DropOldest`, where the sender
+    /// evicts items from the head.
+    ///
+    /// [`commit`]: Receiver::commit
+    /// [`want_recv`]: Receiver::want_recv
+    /// [`try_recv`]: Receiver::try_recv
+    pub fn set_batch_recv(&mut self, batch: bool) {
+        unsafe { self.inner.ring.set_defer_read(batch) };
+        if !batch {
+            self.inner.commit_read();
+        }
+    }
+
+    /// Releases the slots of the items received since the last commit, and notifies
+    /// the sender, see [`set_batch_recv`].
+    ///
+    /// [`set_batch_recv`]: Receiver::set_batch_recv
+    pub fn commit(&mut self) {
+        self.inner.commit_read();
     }
 
     pub fn is_closed(&self) -> bool {
-        self.inner.closed.load(Ordering::Acquire)
+        self.inner.is_closed()
+    }
+
+    /// The number of items the channel holds, the same as [`Sender::capacity`].
+    pub fn capacity(&self) -> usize {
+        self.inner.ring.capacity()
+    }
+
+    /// The number of items discarded because the channel was full, see [`FullPolicy`].
+    pub fn discarded(&self) -> usize {
+        self.inner.ring.discarded()
     }
 
     pub fn close(&mut self) {
-        self.inner.closed.store(true, Ordering::Release)
+        unsafe { self.inner.ring.commit_read() };
+        self.inner.set_closed(true);
+        // the sender may be waiting for free space.
+        self.inner.producer.wake_by_ref();
+    }
+
+    /// Calls `notify` when there are items to receive, or the channel is closed, for
+    /// schedulers which are neither async runtimes nor threads.
+    ///
+    /// The callback is registered where the waker of a receiving task would be, and fires
+    /// once per registration. It is registered right away, and again each time [`try_recv`]
+    /// finds the channel empty, so the scheduler drains the channel with [`try_recv`] on every
+    /// call. The callback may fire on the sender's thread, or right away on this thread if
+    /// there are items already, so it should only schedule the drain. Like the other batch
+    /// methods, [`try_recv`] doesn't notify a waiting sender, [`try_recv_many`] does.
+    ///
+    /// An async method polled afterwards registers its own task instead, until the channel
+    /// is found empty again.
+    ///
+    /// [`try_recv`]: Receiver::try_recv
+    /// [`try_recv_many`]: Receiver::try_recv_many
+    #[cfg(feature = "std")]
+    pub fn set_notify(&mut self, notify: std::sync::Arc<dyn Fn() + Send + Sync>) {
+        self.notify = Some(notify::waker(notify));
+        self.arm_notify();
+    }
+
+    /// Registers the `set_notify` callback, then checks again like `poll_recv` does.
+    fn arm_notify(&self) {
+        if let Some(notify) = &self.notify {
+            self.inner.consumer.register(notify);
+            if !self.inner.ring.is_empty() || self.is_closed() {
+                notify.wake_by_ref();
+            }
+        }
+    }
+
+    /// Wait until there is an item to read, returns `false` if the channel is closed and empty.
+    ///
+    /// The readable items are claimed, so they stay in the ring until the receiver pops them.
+    pub(crate) fn poll_readable(&mut self, cx: &mut Context<'_>) -> Poll<bool> {
+        if self.inner.ring.readable_len() > 0 {
+            return Poll::Ready(true);
+        }
+
+        self.inner.commit_read();
+        self.inner.consumer.register(cx.waker());
+
+        // The same as `poll_recv`, check again after registering.
+        if self.is_closed() {
+            Poll::Ready(self.inner.ring.readable_len() > 0)
+        } else if self.inner.ring.readable_len() == 0 {
+            Poll::Pending
+        } else {
+            Poll::Ready(true)
+        }
     }
 
-    fn poll_next_msg(&self) -> Poll<T> {
-        match self.inner.ring.try_pop() {
+    fn poll_next_msg(&self) -> Poll<(u64, T)> {
+        match self.inner.ring.try_pop_seq() {
             None => Poll::Pending,
             Some(item) => {
-                self.inner.producer.wake_by_ref();
+                self.inner.popped();
                 Poll::Ready(item)
             }
         }
     }
 }
+
+#[cfg(feature = "std")]
+impl<T, I: Indexer, W: WaitStrategy> Receiver<'_, T, I, W> {
+    /// Receives the next value, parking the current thread until one is available.
+    ///
+    /// This is the synchronous version of [`recv`]. `None` is returned once the channel
+    /// is closed and all the remaining values are consumed.
+    ///
+    /// [`recv`]: Receiver::recv
+    pub fn blocking_recv(&mut self) -> Option<T> {
+        blocking::block_on_with(parker(&self.inner, |shared| &shared.consumer), |cx| {
+            self.poll_recv(cx)
+        })
+    }
+
+    /// Receives the next value, parking the current thread for at most `timeout`.
+    ///
+    /// Returns `Err(TryRecvError::Empty)` if no value arrives before the timeout elapses.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, TryRecvError> {
+        match blocking::block_on_timeout_with(
+            parker(&self.inner, |shared| &shared.consumer),
+            timeout,
+            |cx| self.poll_recv(cx),
+        ) {
+            Some(Some(item)) => Ok(item),
+            Some(None) => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use crate::bounded::{channel, P2Receiver, P2Sender};
+use crate::error::SendError;
+use std::io;
+use std::mem::MaybeUninit;
+use std::ptr;
+use std::task::{Context, Poll};
+
+/// Creates an in-memory byte pipe, which can buffer at least `size` bytes.
+///
+/// The pipe is a bounded channel of `u8`, the bytes are copied in and out of the ring
+/// directly, one region at a time, so there is at most one wake up for each read or write.
+///
+/// With the `futures-io` or `tokio` feature, the [`PipeWriter`] implements `AsyncWrite`,
+/// and the [`PipeReader`] implements `AsyncRead` and `AsyncBufRead`.
+pub fn pipe(size: usize) -> (PipeWriter, PipeReader) {
+    from_channel(channel(size))
+}
+
+pub(super) fn from_channel((tx, rx): (P2Sender<u8>, P2Receiver<u8>)) -> (PipeWriter, PipeReader) {
+    (PipeWriter { inner: tx }, PipeReader { inner: rx })
+}
+
+/// The writing half of a [`pipe`].
+///
+/// Dropping the writer, or shutting it down, makes the reader see the end of the stream
+/// once the buffered bytes are read.
+pub struct PipeWriter {
+    inner: P2Sender<u8>,
+}
+
+impl PipeWriter {
+    /// Writes as many bytes as the free space allows, waiting if the pipe is full.
+    ///
+    /// Fails with `BrokenPipe` once the reader is gone.
+    pub fn poll_write(&mut self, cx: &mut Context<'_>, buf: &[u8]) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        match self.inner.poll_next_pos(cx) {
+            Poll::Ready(Ok(_)) => {
+                // The receiver only makes more room, so the slice can't be rejected.
+                Poll::Ready(self.inner.send_slice(buf).map_err(broken_pipe))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(broken_pipe(err))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// The written bytes are visible to the reader right away, so there is nothing to flush.
+    pub fn poll_flush(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Poll::Ready(Ok(()))
+    }
+
+    /// Closes the pipe, further writes fail with `BrokenPipe`.
+    pub fn poll_shutdown(&mut self, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.inner.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The reading half of a [`pipe`].
+pub struct PipeReader {
+    inner: P2Receiver<u8>,
+}
+
+impl PipeReader {
+    /// Reads the buffered bytes into `buf`, waiting if the pipe is empty.
+    ///
+    /// Returns `0` once the writer is gone and all the bytes are read.
+    pub fn poll_read(&mut self, cx: &mut Context<'_>, buf: &mut [u8]) -> Poll<io::Result<usize>> {
+        // Safety: only initialized bytes are written through the view.
+        let buf = unsafe { &mut *(buf as *mut [u8] as *mut [MaybeUninit<u8>]) };
+        self.poll_read_uninit(cx, buf)
+    }
+
+    /// The same as `poll_read`, for a buffer which may not be initialized yet. The bytes
+    /// before the returned length are initialized then.
+    fn poll_read_uninit(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut [MaybeUninit<u8>],
+    ) -> Poll<io::Result<usize>> {
+        if buf.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+
+        match self.poll_fill_buf(cx) {
+            Poll::Ready(Ok(_)) => {
+                let (left, right) = self.inner.read_slices();
+                let first = left.len().min(buf.len());
+                let second = right.len().min(buf.len() - first);
+                let dst = buf.as_mut_ptr().cast::<u8>();
+                // Safety: both copies fit in `buf`, which doesn't overlap the ring.
+                unsafe {
+                    ptr::copy_nonoverlapping(left.as_ptr(), dst, first);
+                    ptr::copy_nonoverlapping(right.as_ptr(), dst.add(first), second);
+                }
+
+                self.consume(first + second);
+                Poll::Ready(Ok(first + second))
+            }
+            Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Returns the buffered bytes in place, waiting if the pipe is empty.
+    ///
+    /// Only the bytes before the end of the ring are returned, unless the pipe is created by
+    /// `mirrored_pipe`. An empty slice means the end of the stream. Use [`consume`] to drop the bytes after reading them.
+    ///
+    /// [`consume`]: PipeReader::consume
+    pub fn poll_fill_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+        match self.inner.poll_readable(cx) {
+            Poll::Ready(true) => Poll::Ready(Ok(self.inner.read_slices().0)),
+            Poll::Ready(false) => Poll::Ready(Ok(&[])),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+
+    /// Drops the first `amt` buffered bytes, and notifies the writer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are fewer than `amt` buffered bytes.
+    pub fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt)
+    }
+}
+
+fn broken_pipe(err: SendError) -> io::Error {
+    io::Error::new(io::ErrorKind::BrokenPipe, err)
+}
+
+#[cfg(feature = "futures-io")]
+mod futures_io_impl {
+    use super::{PipeReader, PipeWriter};
+    use futures_io::{AsyncBufRead, AsyncRead, AsyncWrite};
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+
+    impl AsyncWrite for PipeWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_write(cx, buf)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().poll_flush(cx)
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().poll_shutdown(cx)
+        }
+    }
+
+    impl AsyncRead for PipeReader {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut [u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_read(cx, buf)
+        }
+    }
+
+    impl AsyncBufRead for PipeReader {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+            self.get_mut().poll_fill_buf(cx)
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            self.get_mut().consume(amt)
+        }
+    }
+}
+
+#[cfg(feature = "tokio")]
+mod tokio_impl {
+    use super::{PipeReader, PipeWriter};
+    use std::io;
+    use std::pin::Pin;
+    use std::task::{Context, Poll};
+    use tokio::io::{AsyncBufRead, AsyncRead, AsyncWrite, ReadBuf};
+
+    impl AsyncWrite for PipeWriter {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            self.get_mut().poll_write(cx, buf)
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().poll_flush(cx)
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            self.get_mut().poll_shutdown(cx)
+        }
+    }
+
+    impl AsyncRead for PipeReader {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            // Safety: `poll_read_uninit` never de-initializes the bytes.
+            let unfilled = unsafe { buf.unfilled_mut() };
+            let n = match self.get_mut().poll_read_uninit(cx, unfilled) {
+                Poll::Ready(Ok(n)) => n,
+                Poll::Ready(Err(err)) => return Poll::Ready(Err(err)),
+                Poll::Pending => return Poll::Pending,
+            };
+            // Safety: the first `n` bytes are written by `poll_read_uninit`.
+            unsafe { buf.assume_init(n) };
+            buf.advance(n);
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    impl AsyncBufRead for PipeReader {
+        fn poll_fill_buf(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<&[u8]>> {
+            self.get_mut().poll_fill_buf(cx)
+        }
+
+        fn consume(self: Pin<&mut Self>, amt: usize) {
+            self.get_mut().consume(amt)
+        }
+    }
+}
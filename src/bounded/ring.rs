@@ -1,57 +1,113 @@
-use self::inner::AtomicPos;
-use crate::loom::UnsafeCell;
-use core::cmp;
-use std::mem::MaybeUninit;
-use std::slice::from_raw_parts_mut;
-use std::sync::atomic::Ordering;
-
-#[cfg(feature = "cache-padded")]
-mod inner {
-    use crate::loom::AtomicUsize;
-    use cache_padded::CachePadded;
-    use core::ops::Deref;
+use super::FullPolicy;
+#[cfg(not(loom))]
+use crate::allocator::{AllocBuf, AllocError, Allocator};
+use crate::cache_padded::CachePadded;
+use crate::loom::{AtomicPosition, Counter, UnsafeCell};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::mem::MaybeUninit;
+use core::ops::Deref;
+#[cfg(not(loom))]
+use core::ptr::NonNull;
+use core::slice::{from_raw_parts, from_raw_parts_mut};
+use core::sync::atomic::Ordering;
+use core::{cmp, ptr};
+
+/// The position of one side, and its cached copy of the position of the other side.
+///
+/// All of them are only written by the owning side, so they share its cache line, and the
+/// other side is only reloaded when the cached position says the ring is full or empty.
+pub(crate) struct Side<C = AtomicPosition> {
+    /// The published position, which the other side sees.
+    pos: C,
+    /// Where the owning side really is, ahead of `pos` until it commits.
+    local: UnsafeCell<usize>,
+    cached: UnsafeCell<usize>,
+    /// The number of items the owning side moved over. The positions wrap around, so the
+    /// sequence numbers are counted here, by the producer when the ring stores them and by
+    /// the consumer otherwise.
+    total: UnsafeCell<u64>,
+}
 
-    #[derive(Default)]
-    pub(crate) struct AtomicPos {
-        inner: CachePadded<AtomicUsize>,
+impl<C: Default> Default for Side<C> {
+    fn default() -> Self {
+        Self {
+            pos: C::default(),
+            local: UnsafeCell::new(0),
+            cached: UnsafeCell::new(0),
+            total: UnsafeCell::new(0),
+        }
     }
+}
 
-    impl Deref for AtomicPos {
-        type Target = AtomicUsize;
+impl<C> Deref for Side<C> {
+    type Target = C;
 
-        fn deref(&self) -> &Self::Target {
-            &self.inner
-        }
+    fn deref(&self) -> &Self::Target {
+        &self.pos
     }
 }
 
-#[cfg(not(feature = "cache-padded"))]
-mod inner {
-    use crate::loom::AtomicUsize;
-    use core::ops::Deref;
+impl<C> Side<C> {
+    /// # Safety
+    ///
+    /// Only the side owning the position can access the cache.
+    unsafe fn cached(&self) -> usize {
+        self.cached.with(|ptr| *ptr)
+    }
 
-    #[derive(Default)]
-    pub(crate) struct AtomicPos {
-        inner: AtomicUsize,
+    /// # Safety
+    ///
+    /// The same as `cached`.
+    unsafe fn set_cached(&self, pos: usize) {
+        self.cached.with_mut(|ptr| *ptr = pos)
     }
 
-    impl Deref for AtomicPos {
-        type Target = AtomicUsize;
+    /// # Safety
+    ///
+    /// The same as `cached`.
+    unsafe fn local(&self) -> usize {
+        self.local.with(|ptr| *ptr)
+    }
 
-        fn deref(&self) -> &Self::Target {
-            &self.inner
-        }
+    /// # Safety
+    ///
+    /// The same as `cached`.
+    unsafe fn set_local(&self, pos: usize) {
+        self.local.with_mut(|ptr| *ptr = pos)
+    }
+
+    /// # Safety
+    ///
+    /// The same as `cached`.
+    unsafe fn total(&self) -> u64 {
+        self.total.with(|ptr| *ptr)
+    }
+
+    /// # Safety
+    ///
+    /// The same as `cached`.
+    unsafe fn add_total(&self, n: usize) {
+        self.total
+            .with_mut(|ptr| *ptr = (*ptr).wrapping_add(n as u64))
     }
 }
 
-unsafe impl<T: Send, I: Send + Indexer> Send for Ring<T, I> {}
+unsafe impl<T: Send, I: Send + Indexer, C: Send + Counter> Send for Ring<T, I, C> {}
 
-unsafe impl<T: Send, I: Send + Indexer> Sync for Ring<T, I> {}
+unsafe impl<T: Send, I: Send + Indexer, C: Sync + Counter> Sync for Ring<T, I, C> {}
 
+/// Maps the positions to the slots, and moves them around the ring.
 pub trait Indexer {
     fn index(&self, pos: usize) -> usize;
 
     fn cap(&self) -> usize;
+
+    /// The position `n <= cap` slots after `pos`, the ring masks it below the busy bit.
+    fn advance(&self, pos: usize, n: usize) -> usize;
+
+    /// The number of slots from `from` to `to`, the ring masks it below the busy bit.
+    fn distance(&self, from: usize, to: usize) -> usize;
 }
 
 pub struct And {
@@ -59,6 +115,15 @@ pub struct And {
     cap: usize,
 }
 
+impl And {
+    /// `cap` must be a power of two.
+    pub(crate) fn new(cap: usize) -> Self {
+        debug_assert!(cap.is_power_of_two());
+        Self { mask: cap - 1, cap }
+    }
+}
+
+/// The positions wrap around with the integer, which the capacity divides.
 impl Indexer for And {
     fn index(&self, pos: usize) -> usize {
         pos & self.mask
@@ -67,126 +132,706 @@ impl Indexer for And {
     fn cap(&self) -> usize {
         self.cap
     }
+
+    fn advance(&self, pos: usize, n: usize) -> usize {
+        pos.wrapping_add(n)
+    }
+
+    fn distance(&self, from: usize, to: usize) -> usize {
+        to.wrapping_sub(from)
+    }
 }
 
+/// Any capacity, the positions stay in `[0, 2 * cap)` and wrap around with a conditional
+/// subtract instead of a division. Two laps of positions still tell a full ring from an
+/// empty one, and the integer never wraps under them.
 pub struct Remainder {
     cap: usize,
 }
 
+impl Remainder {
+    pub(crate) fn new(cap: usize) -> Self {
+        Self { cap }
+    }
+
+    fn period(&self) -> usize {
+        self.cap * 2
+    }
+}
+
 impl Indexer for Remainder {
     fn index(&self, pos: usize) -> usize {
-        pos % self.cap
+        debug_assert!(pos < self.period());
+        if pos >= self.cap {
+            pos - self.cap
+        } else {
+            pos
+        }
     }
 
     fn cap(&self) -> usize {
         self.cap
     }
+
+    fn advance(&self, pos: usize, n: usize) -> usize {
+        let pos = pos + n;
+        if pos >= self.period() {
+            pos - self.period()
+        } else {
+            pos
+        }
+    }
+
+    fn distance(&self, from: usize, to: usize) -> usize {
+        if to >= from {
+            to - from
+        } else {
+            to + self.period() - from
+        }
+    }
 }
 
-pub(crate) type ExactRing<T> = Ring<T, Remainder>;
-pub(crate) type P2Ring<T> = Ring<T, And>;
+pub(crate) type ExactRing<T, C = AtomicPosition> = Ring<T, Remainder, C>;
+pub(crate) type P2Ring<T, C = AtomicPosition> = Ring<T, And, C>;
+
+/// A slot of the ring. The slices of slots view them as plain values, but a loom cell keeps
+/// its state next to the value, so under loom the slots are plain cells, and `Ring::tracks`
+/// checks the accesses instead.
+#[cfg(not(loom))]
+pub(crate) type Slot<T> = UnsafeCell<MaybeUninit<T>>;
+#[cfg(loom)]
+pub(crate) type Slot<T> = core::cell::UnsafeCell<MaybeUninit<T>>;
+
+/// The memory behind the slots of a ring.
+pub(crate) enum Storage<T> {
+    Heap(Box<[Slot<T>]>),
+    /// Mapped twice back to back, so a range of slots never needs to be split.
+    #[cfg(all(target_os = "linux", feature = "mirror", not(loom)))]
+    Mirrored(super::mirror::MirroredBuf<T>),
+    /// Slots owned by a `StaticChannel`, or the buffer given to `channel_in`.
+    #[cfg(not(loom))]
+    Borrowed(NonNull<Slot<T>>, usize),
+    /// From the allocator given to `channel_with_alloc`.
+    #[cfg(not(loom))]
+    Allocated(AllocBuf<T>),
+}
 
-pub(crate) struct Ring<T, I: Indexer> {
-    buf: Box<[UnsafeCell<MaybeUninit<T>>]>,
-    indexer: I,
-    producer_pos: AtomicPos,
-    consumer_pos: AtomicPos,
+impl<T> Storage<T> {
+    fn heap(cap: usize) -> Self {
+        Storage::Heap((0..cap).map(|_| Slot::new(MaybeUninit::uninit())).collect())
+    }
+
+    fn is_mirrored(&self) -> bool {
+        match self {
+            Storage::Heap(_) => false,
+            #[cfg(all(target_os = "linux", feature = "mirror", not(loom)))]
+            Storage::Mirrored(_) => true,
+            #[cfg(not(loom))]
+            Storage::Borrowed(..) | Storage::Allocated(_) => false,
+        }
+    }
 }
 
-impl<T> Ring<T, And> {
-    pub(crate) fn with_capacity(cap: usize) -> Self {
-        let cap = cmp::max(cap + 1, 2)
-            .checked_next_power_of_two()
-            .expect("capacity overflow");
+impl<T> Deref for Storage<T> {
+    type Target = [Slot<T>];
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Storage::Heap(buf) => buf,
+            // `UnsafeCell` is transparent, so the mapping can be viewed as cells.
+            #[cfg(all(target_os = "linux", feature = "mirror", not(loom)))]
+            Storage::Mirrored(buf) => unsafe {
+                core::slice::from_raw_parts(buf.as_ptr().cast(), buf.cap())
+            },
+            #[cfg(not(loom))]
+            Storage::Borrowed(ptr, cap) => unsafe {
+                core::slice::from_raw_parts(ptr.as_ptr(), *cap)
+            },
+            #[cfg(not(loom))]
+            Storage::Allocated(buf) => unsafe {
+                core::slice::from_raw_parts(buf.as_ptr().cast(), buf.cap())
+            },
+        }
+    }
+}
 
-        let indexer = And { mask: cap - 1, cap };
+/// The capacity of a power of two ring, which can hold at least `cap` items, and at least one.
+pub(crate) fn p2_capacity(cap: usize) -> usize {
+    cmp::max(cap, 1)
+        .checked_next_power_of_two()
+        .expect("capacity overflow")
+}
 
-        Self::new(indexer)
+/// The positions and counters are `C`, atomics unless the ring is used by a `local` channel.
+pub(crate) struct Ring<T, I: Indexer, C: Counter = AtomicPosition> {
+    buf: Storage<T>,
+    /// One loom cell for each slot, which stands for it in the checked accesses.
+    #[cfg(loom)]
+    tracks: Box<[UnsafeCell<()>]>,
+    indexer: I,
+    /// Written by the producer, with its cached consumer position.
+    producer_pos: CachePadded<Side<C>>,
+    /// Written by the consumer, with its cached producer position. The producer also moves
+    /// it when it evicts items under `DropOldest`, so the local position is unused then.
+    consumer_pos: CachePadded<Side<C>>,
+    policy: FullPolicy,
+    /// Whether the consumer only publishes its position in `commit_read`, only touched
+    /// by the consumer.
+    defer_read: UnsafeCell<bool>,
+    /// The sequence number of each slot, only allocated when items can be rejected,
+    /// otherwise the sequence number is the position itself.
+    seqs: Option<Box<[UnsafeCell<u64>]>>,
+    /// Items evicted by the producer under `DropOldest`.
+    evicted: C,
+    /// Items rejected by the producer under `DropNewest`, or under `DropOldest`
+    /// when the head is borrowed.
+    rejected: C,
+}
+
+impl<T, C: Counter> Ring<T, And, C> {
+    pub(crate) fn with_capacity(cap: usize, policy: FullPolicy) -> Self {
+        Self::new(And::new(p2_capacity(cap)), policy)
+    }
+
+    #[cfg(not(loom))]
+    pub(crate) fn with_capacity_in(
+        cap: usize,
+        policy: FullPolicy,
+        alloc: Box<dyn Allocator>,
+    ) -> Result<Self, AllocError> {
+        Self::new_in(And::new(p2_capacity(cap)), policy, alloc)
     }
 }
 
-impl<T> Ring<T, Remainder> {
-    pub(crate) fn with_capacity(cap: usize) -> Self {
-        let indexer = Remainder {
-            cap: cmp::max(cap + 1, 2),
-        };
-        Self::new(indexer)
+impl<T, C: Counter> Ring<T, Remainder, C> {
+    pub(crate) fn with_capacity(cap: usize, policy: FullPolicy) -> Self {
+        let indexer = Remainder::new(cmp::max(cap, 1));
+        Self::new(indexer, policy)
+    }
+
+    #[cfg(not(loom))]
+    pub(crate) fn with_capacity_in(
+        cap: usize,
+        policy: FullPolicy,
+        alloc: Box<dyn Allocator>,
+    ) -> Result<Self, AllocError> {
+        let indexer = Remainder::new(cmp::max(cap, 1));
+        Self::new_in(indexer, policy, alloc)
+    }
+
+    /// A ring over `cap > 0` slots which it doesn't own.
+    ///
+    /// # Safety
+    ///
+    /// The slots must stay valid while the ring lives, and only be accessed through it.
+    #[cfg(not(loom))]
+    pub(crate) unsafe fn from_slots(slots: NonNull<MaybeUninit<T>>, cap: usize) -> Self {
+        let indexer = Remainder::new(cap);
+        Self::from_storage(
+            Storage::Borrowed(slots.cast(), cap),
+            indexer,
+            FullPolicy::Wait,
+        )
     }
 }
 
-impl<T, I: Indexer> Drop for Ring<T, I> {
+impl<T, I: Indexer, C: Counter> Drop for Ring<T, I, C> {
     fn drop(&mut self) {
-        unsafe {
-            let (left, right) = self.as_mut_slice();
-            std::ptr::drop_in_place(right);
-            std::ptr::drop_in_place(left);
-        }
+        while self.try_pop().is_some() {}
     }
 }
 
-impl<T, I: Indexer> Ring<T, I> {
-    fn new(indexer: I) -> Self {
-        let buf = (0..indexer.cap())
-            .map(|_| UnsafeCell::new(MaybeUninit::uninit()))
-            .collect();
+impl<T, I: Indexer, C: Counter> Ring<T, I, C> {
+    /// Set in `consumer_pos` while the consumer is reading the head items, so the producer
+    /// must not evict them. Only used by the `DropOldest` policy.
+    const BUSY: usize = 1 << (C::BITS - 1);
+
+    /// Positions wrap around below the busy bit.
+    const POS_MASK: usize = Self::BUSY - 1;
+
+    pub(crate) fn advance(&self, pos: usize, n: usize) -> usize {
+        self.indexer.advance(pos, n) & Self::POS_MASK
+    }
+
+    fn distance(&self, from: usize, to: usize) -> usize {
+        self.indexer.distance(from, to) & Self::POS_MASK
+    }
+
+    fn new(indexer: I, policy: FullPolicy) -> Self {
+        Self::from_storage(Storage::heap(indexer.cap()), indexer, policy)
+    }
+
+    #[cfg(not(loom))]
+    fn new_in(
+        indexer: I,
+        policy: FullPolicy,
+        alloc: Box<dyn Allocator>,
+    ) -> Result<Self, AllocError> {
+        let buf = AllocBuf::new(indexer.cap(), alloc)?;
+        Ok(Self::from_storage(Storage::Allocated(buf), indexer, policy))
+    }
+
+    pub(crate) fn from_storage(buf: Storage<T>, indexer: I, policy: FullPolicy) -> Self {
+        // two laps of positions must fit below the busy bit.
+        assert!(indexer.cap() <= Self::BUSY / 2, "capacity overflow");
+        let seqs = match policy {
+            FullPolicy::DropNewest | FullPolicy::DropOldest => {
+                Some((0..indexer.cap()).map(|_| UnsafeCell::new(0)).collect())
+            }
+            FullPolicy::Wait | FullPolicy::Error => None,
+        };
         Self {
             buf,
+            #[cfg(loom)]
+            tracks: (0..indexer.cap()).map(|_| UnsafeCell::new(())).collect(),
             consumer_pos: Default::default(),
             producer_pos: Default::default(),
             indexer,
+            policy,
+            defer_read: UnsafeCell::new(false),
+            seqs,
+            evicted: Default::default(),
+            rejected: Default::default(),
         }
     }
 
+    pub(crate) fn policy(&self) -> FullPolicy {
+        self.policy
+    }
+
+    /// Whether the producer has no free slot, counting the staged items.
+    /// Only the producer calls this.
     pub(crate) fn is_full(&self) -> bool {
-        self.capacity() - self.len() == 1
+        self.is_full_from(self.write_pos(), self.consumer_pos())
     }
 
+    /// Whether the consumer has read all the published items. Only the consumer calls this.
     pub(crate) fn is_empty(&self) -> bool {
-        self.consumer_pos() == self.producer_pos()
+        self.read_pos() == self.producer_pos()
+    }
+
+    /// Whether the consumer has released all the items written by the producer, staged
+    /// or not. Only the producer calls this.
+    pub(crate) fn is_drained(&self) -> bool {
+        self.consumer_pos() == self.write_pos()
     }
 
     pub(crate) fn next_idx(&self) -> Option<usize> {
-        if self.is_full() {
-            None
-        } else {
-            let next = self.producer_pos();
-            Some(self.index(next))
+        let p = self.write_pos();
+        if self.is_full_from(p, unsafe { self.producer_pos.cached() }) {
+            // The consumer may have moved on since the last load.
+            let c = self.consumer_pos();
+            unsafe { self.producer_pos.set_cached(c) };
+            if self.is_full_from(p, c) {
+                return None;
+            }
         }
+        Some(self.index(p))
+    }
+
+    /// Every slot holds an item then. The positions tell it from an empty ring, since they
+    /// wrap around after more than one lap.
+    fn is_full_from(&self, producer_pos: usize, consumer_pos: usize) -> bool {
+        self.distance(consumer_pos, producer_pos) == self.capacity()
     }
 
     pub(crate) fn try_pop(&self) -> Option<T> {
+        self.try_pop_seq().map(|(_, item)| item)
+    }
+
+    /// Pops the head item together with its sequence number.
+    pub(crate) fn try_pop_seq(&self) -> Option<(u64, T)> {
+        if self.policy != FullPolicy::DropOldest {
+            return self.try_pop_cached();
+        }
+
         if self.is_empty() {
-            None
-        } else {
-            unsafe {
-                let now = self.consumer_pos.load(Ordering::Acquire);
-                let idx = self.index(now);
-                self.consumer_pos.store(now + 1, Ordering::Release);
-                Some(self.buffer_read(idx))
+            return None;
+        }
+
+        let now = self.claim_head();
+        // The producer may be in the middle of evicting the last item.
+        if now == self.producer_pos() {
+            self.release_head(now, 0);
+            return None;
+        }
+
+        unsafe {
+            let idx = self.index(now);
+            let seq = self.seq_at(now);
+            // read the item before releasing the slot, otherwise the producer may overwrite it.
+            let item = self.buffer_read(idx);
+            self.release_head(now, 1);
+            Some((seq, item))
+        }
+    }
+
+    /// `try_pop_seq` when only the consumer moves its position, so it can be loaded relaxed,
+    /// and the producer position is only reloaded when the cached one says the ring is empty.
+    ///
+    /// Under `DropOldest` the producer may move the consumer position past the cached
+    /// producer position, so the cache can't be used there.
+    fn try_pop_cached(&self) -> Option<(u64, T)> {
+        let now = unsafe { self.consumer_pos.local() };
+        if now == unsafe { self.consumer_pos.cached() } {
+            let p = self.producer_pos();
+            unsafe { self.consumer_pos.set_cached(p) };
+            if now == p {
+                return None;
+            }
+        }
+
+        unsafe {
+            let seq = self.seq_at(now);
+            let item = self.buffer_read(self.index(now));
+            self.release_head(now, 1);
+            Some((seq, item))
+        }
+    }
+
+    /// Claims the head items for the consumer, and returns the number of readable items.
+    ///
+    /// Under `DropOldest`, the claimed items can't be evicted by the producer until the
+    /// consumer pops them, so they are safe to borrow.
+    pub(crate) fn readable_len(&self) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+
+        let now = self.claim_head();
+        let len = self.readable_from(now);
+        if len == 0 {
+            // Don't hold the claim while there is nothing to read, the producer would
+            // have to reject items until the next pop.
+            self.release_head(now, 0);
+        }
+        len
+    }
+
+    /// Evicts the head item to make room for the next one, as `DropOldest` requires.
+    ///
+    /// Returns the index of the free slot, or `None` if the head is claimed by the consumer
+    /// and the next item should be rejected instead. The staged items must be committed
+    /// first, otherwise the head may be an item the consumer can't see yet.
+    pub(crate) fn evict_oldest(&self) -> Option<usize> {
+        let p = self.write_pos();
+        debug_assert_eq!(p, self.producer_pos());
+        let mut now = self.consumer_pos.load(Ordering::Acquire);
+        loop {
+            if self.distance(now & Self::POS_MASK, p) != self.capacity() {
+                // the consumer has popped some items in the meantime.
+                unsafe { self.producer_pos.set_cached(now & Self::POS_MASK) };
+                return Some(self.index(p));
+            }
+
+            if now & Self::BUSY != 0 {
+                return None;
+            }
+
+            match self.consumer_pos.compare_exchange(
+                now,
+                self.advance(now, 1),
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    // Safety: the slot is released by the consumer, and it can't claim it anymore.
+                    unsafe {
+                        drop(self.buffer_read(self.index(now)));
+                        self.producer_pos.set_cached(self.advance(now, 1));
+                    }
+                    self.evicted.fetch_add(1, Ordering::Relaxed);
+                    return Some(self.index(p));
+                }
+                Err(actual) => now = actual,
             }
         }
     }
 
-    pub(crate) unsafe fn set_unchecked(&self, t: T, idx: usize) {
+    /// Records an item the producer gave up on, so the next sequence number skips it.
+    pub(crate) fn reject(&self) {
+        self.rejected.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The number of items evicted or rejected so far.
+    pub(crate) fn discarded(&self) -> usize {
+        self.evicted
+            .load(Ordering::Relaxed)
+            .wrapping_add(self.rejected.load(Ordering::Relaxed))
+    }
+
+    /// Writes the item into the slot `idx`, it is only published with the next commit.
+    pub(crate) unsafe fn stage_unchecked(&self, t: T, idx: usize) {
         self.buffer_write(idx, t);
-        let now = self.producer_pos.load(Ordering::Acquire);
-        self.producer_pos.store(now + 1, Ordering::Release);
+        self.stage(1);
     }
 
-    fn len(&self) -> usize {
-        let diff = self.producer_pos().wrapping_sub(self.consumer_pos());
-        self.index(diff)
+    /// The number of slots the producer can write before the ring is full.
+    ///
+    /// Only the producer calls this, and the consumer position it loads also refreshes the
+    /// cached one, which must never fall a whole ring behind the producer position.
+    pub(crate) fn vacant_len(&self) -> usize {
+        let c = self.consumer_pos();
+        unsafe { self.producer_pos.set_cached(c) };
+        let len = self.distance(c, self.write_pos());
+        self.capacity() - len
     }
 
-    fn producer_idx(&self) -> usize {
-        self.index(self.producer_pos())
+    /// Make the next `n` slots after the write position visible to the consumer, together
+    /// with the staged items before them.
+    ///
+    /// # Safety
+    ///
+    /// The caller must be the only producer, and the `n` slots must be initialized.
+    pub(crate) unsafe fn publish(&self, n: usize) {
+        self.stage(n);
+        self.commit_write();
     }
 
-    fn consumer_idx(&self) -> usize {
-        self.index(self.consumer_pos())
+    /// Moves the write position over the next `n` slots, without publishing them.
+    ///
+    /// The sequence numbers are written here, so the items rejected before the commit
+    /// don't shift them.
+    ///
+    /// # Safety
+    ///
+    /// The same as `publish`.
+    unsafe fn stage(&self, n: usize) {
+        let now = self.write_pos();
+        if let Some(seqs) = &self.seqs {
+            let first = self
+                .producer_pos
+                .total()
+                .wrapping_add(self.rejected.load(Ordering::Relaxed) as u64);
+            for i in 0..n {
+                let pos = self.advance(now, i);
+                let seq = first.wrapping_add(i as u64);
+                seqs.get_unchecked(self.index(pos))
+                    .with_mut(|ptr| ptr.write(seq));
+            }
+            self.producer_pos.add_total(n);
+        }
+        self.producer_pos.set_local(self.advance(now, n));
     }
 
-    fn index(&self, pos: usize) -> usize {
+    /// Publishes the staged items, returns whether there were any.
+    ///
+    /// # Safety
+    ///
+    /// The caller must be the only producer.
+    pub(crate) unsafe fn commit_write(&self) -> bool {
+        let now = self.producer_pos.local();
+        if now == self.producer_pos.load(Ordering::Relaxed) {
+            return false;
+        }
+        self.producer_pos.store(now, Ordering::Release);
+        true
+    }
+
+    /// Publishes the items popped since the last commit, returns whether there were any.
+    /// This only does something after `set_defer_read(true)`.
+    ///
+    /// # Safety
+    ///
+    /// The caller must be the only consumer.
+    pub(crate) unsafe fn commit_read(&self) -> bool {
+        if self.policy == FullPolicy::DropOldest {
+            return false;
+        }
+        let now = self.consumer_pos.local();
+        if now == self.consumer_pos.load(Ordering::Relaxed) {
+            return false;
+        }
+        self.consumer_pos.store(now, Ordering::Release);
+        true
+    }
+
+    /// Whether the popped items are only released to the producer by `commit_read`.
+    /// Under `DropOldest`, the producer moves the consumer position too, so the items are
+    /// always released right away.
+    ///
+    /// # Safety
+    ///
+    /// The caller must be the only consumer.
+    pub(crate) unsafe fn set_defer_read(&self, defer: bool) {
+        self.defer_read.with_mut(|ptr| *ptr = defer);
+    }
+
+    /// Forgets what the sides kept to themselves, for the sides of a new split: the batch
+    /// receive mode is turned off, and the local and cached positions are set back to the
+    /// published ones.
+    ///
+    /// # Safety
+    ///
+    /// No side may be alive, and the sides must have committed before they were gone.
+    #[cfg(not(loom))]
+    pub(crate) unsafe fn reset_sides(&self) {
+        self.set_defer_read(false);
+        let p = self.producer_pos();
+        let c = self.consumer_pos();
+        self.producer_pos.set_local(p);
+        self.producer_pos.set_cached(c);
+        self.consumer_pos.set_local(c);
+        self.consumer_pos.set_cached(p);
+    }
+
+    /// Whether the popped items wait for `commit_read`, see `set_defer_read`.
+    /// Only the consumer calls this.
+    pub(crate) fn defers_read(&self) -> bool {
+        self.policy != FullPolicy::DropOldest && unsafe { self.defer_read.with(|ptr| *ptr) }
+    }
+
+    /// The start and the length of the slots from `pos` to `pos + len`, split in two when the
+    /// range wraps around. With the mirrored storage, the second part is always empty.
+    ///
+    /// # Safety
+    ///
+    /// `len` must not exceed the capacity.
+    unsafe fn slot_parts(&self, pos: usize, len: usize) -> [(*mut MaybeUninit<T>, usize); 2] {
+        let idx = self.index(pos);
+        let first = if self.buf.is_mirrored() {
+            len
+        } else {
+            cmp::min(len, self.capacity() - idx)
+        };
+        let ptr = self.buf_ptr();
+        [(ptr.add(idx), first), (ptr, len - first)]
+    }
+
+    /// The slots from `pos` to `pos + len`, split into two slices when the range wraps around.
+    /// With the mirrored storage, the second slice is always empty.
+    ///
+    /// # Safety
+    ///
+    /// `len` must not exceed the capacity, and the caller must have exclusive access to
+    /// these slots while the slices are alive.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn slots_mut(
+        &self,
+        pos: usize,
+        len: usize,
+    ) -> (&mut [MaybeUninit<T>], &mut [MaybeUninit<T>]) {
+        // loom can't follow the slices, so only check that the slots are free to borrow.
+        #[cfg(loom)]
+        for i in 0..len {
+            let track = self.tracks.get_unchecked(self.index(self.advance(pos, i)));
+            track.with_mut(|_| ());
+        }
+        let [(left, first), (right, second)] = self.slot_parts(pos, len);
+        (
+            from_raw_parts_mut(left, first),
+            from_raw_parts_mut(right, second),
+        )
+    }
+
+    /// The readable items, split into two slices when they wrap around the end of the ring.
+    ///
+    /// The slices are built from `*const` pointers, so the `&self` methods of the receiver
+    /// never create a `&mut` of the items they share.
+    ///
+    /// # Safety
+    ///
+    /// The caller must be the only consumer, and must neither pop nor mutably borrow the
+    /// items while the slices are alive.
+    pub(crate) unsafe fn readable_slices(&self) -> (&[T], &[T]) {
+        let pos = self.read_pos();
+        let len = self.readable_len();
+        #[cfg(loom)]
+        for i in 0..len {
+            let track = self.tracks.get_unchecked(self.index(self.advance(pos, i)));
+            track.with(|_| ());
+        }
+        let [(left, first), (right, second)] = self.slot_parts(pos, len);
+        (
+            from_raw_parts(left as *const T, first),
+            from_raw_parts(right as *const T, second),
+        )
+    }
+
+    /// The mutable version of `readable_slices`.
+    ///
+    /// # Safety
+    ///
+    /// The caller must be the only consumer, and must not pop items while the slices are alive.
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn readable_slices_mut(&self) -> (&mut [T], &mut [T]) {
+        let len = self.readable_len();
+        let (left, right) = self.slots_mut(self.read_pos(), len);
+        (
+            &mut *(left as *mut [MaybeUninit<T>] as *mut [T]),
+            &mut *(right as *mut [MaybeUninit<T>] as *mut [T]),
+        )
+    }
+
+    /// Drop the first `n` readable items, and release their slots with a single store.
+    ///
+    /// # Safety
+    ///
+    /// The caller must be the only consumer, and there must be at least `n` readable items.
+    pub(crate) unsafe fn consume(&self, n: usize) {
+        let now = self.claim_head();
+        let (left, right) = self.slots_mut(now, n);
+        for slot in left.iter_mut().chain(right) {
+            slot.assume_init_drop();
+        }
+        self.release_head(now, n);
+    }
+
+    /// Move up to `limit` readable items to the end of `buf`, and release their slots
+    /// with a single store.
+    ///
+    /// # Safety
+    ///
+    /// The caller must be the only consumer.
+    pub(crate) unsafe fn pop_into(&self, buf: &mut Vec<T>, limit: usize) -> usize {
+        if self.is_empty() {
+            return 0;
+        }
+
+        let now = self.claim_head();
+        let n = cmp::min(self.readable_from(now), limit);
+        let (left, right) = self.slots_mut(now, n);
+
+        buf.reserve(n);
+        for part in [left, right] {
+            let len = buf.len();
+            ptr::copy_nonoverlapping(part.as_ptr().cast(), buf.as_mut_ptr().add(len), part.len());
+            buf.set_len(len + part.len());
+        }
+
+        self.release_head(now, n);
+        n
+    }
+
+    fn producer_pos(&self) -> usize {
+        self.producer_pos.load(Ordering::Acquire)
+    }
+
+    /// The producer position after the staged items. Only the producer calls this.
+    pub(crate) fn write_pos(&self) -> usize {
+        unsafe { self.producer_pos.local() }
+    }
+
+    /// The consumer position after the items popped but not committed yet.
+    /// Only the consumer calls this.
+    fn read_pos(&self) -> usize {
+        if self.policy == FullPolicy::DropOldest {
+            self.consumer_pos()
+        } else {
+            unsafe { self.consumer_pos.local() }
+        }
+    }
+
+    /// The number of items after the consumer position `now`. Only the consumer calls this,
+    /// and the producer position it loads also refreshes the cached one, so the consumer
+    /// never moves past it.
+    fn readable_from(&self, now: usize) -> usize {
+        let p = self.producer_pos();
+        unsafe { self.consumer_pos.set_cached(p) };
+        self.distance(now, p)
+    }
+
+    pub(crate) fn index(&self, pos: usize) -> usize {
         self.indexer.index(pos)
     }
 
@@ -194,39 +839,104 @@ impl<T, I: Indexer> Ring<T, I> {
         self.indexer.cap()
     }
 
+    #[cfg(not(loom))]
     unsafe fn buffer_read(&self, idx: usize) -> T {
         let ptr = self.buf.as_ptr();
         let ptr = &*ptr.add(idx);
         ptr.with(|inner| inner.read().assume_init())
     }
 
+    #[cfg(loom)]
+    unsafe fn buffer_read(&self, idx: usize) -> T {
+        let slot = self.buf.get_unchecked(idx).get();
+        self.tracks
+            .get_unchecked(idx)
+            .with(|_| slot.read().assume_init())
+    }
+
+    #[cfg(not(loom))]
     unsafe fn buffer_write(&self, idx: usize, value: T) {
         let cell = self.buf.get_unchecked(idx);
         cell.with_mut(|ptr| ptr.write(MaybeUninit::new(value)));
     }
 
+    #[cfg(loom)]
+    unsafe fn buffer_write(&self, idx: usize, value: T) {
+        let slot = self.buf.get_unchecked(idx).get();
+        self.tracks
+            .get_unchecked(idx)
+            .with_mut(|_| slot.write(MaybeUninit::new(value)));
+    }
+
+    fn buf_ptr(&self) -> *mut MaybeUninit<T> {
+        match &self.buf {
+            // `Slot` is transparent, so the buffer can be viewed as `[MaybeUninit<T>]`.
+            Storage::Heap(buf) => buf.as_ptr() as *mut MaybeUninit<T>,
+            // The slice view only covers the first mapping, so take the pointer of the whole range.
+            #[cfg(all(target_os = "linux", feature = "mirror", not(loom)))]
+            Storage::Mirrored(buf) => buf.as_ptr(),
+            #[cfg(not(loom))]
+            Storage::Borrowed(ptr, _) => ptr.as_ptr().cast(),
+            #[cfg(not(loom))]
+            Storage::Allocated(buf) => buf.as_ptr(),
+        }
+    }
+
     fn consumer_pos(&self) -> usize {
-        self.consumer_pos.load(Ordering::Acquire)
+        self.consumer_pos.load(Ordering::Acquire) & Self::POS_MASK
     }
 
-    fn producer_pos(&self) -> usize {
-        self.producer_pos.load(Ordering::Acquire)
+    /// Sets the busy bit under `DropOldest`, so the producer stops evicting the head items.
+    /// Returns the consumer position.
+    fn claim_head(&self) -> usize {
+        if self.policy != FullPolicy::DropOldest {
+            // Only the consumer moves its position then.
+            return self.read_pos();
+        }
+
+        let mut now = self.consumer_pos.load(Ordering::Acquire);
+        loop {
+            if now & Self::BUSY != 0 {
+                return now & Self::POS_MASK;
+            }
+
+            match self.consumer_pos.compare_exchange_weak(
+                now,
+                now | Self::BUSY,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return now,
+                Err(actual) => now = actual,
+            }
+        }
     }
 
-    unsafe fn as_mut_slice(&mut self) -> (&mut [T], &mut [T]) {
-        let contiguous = self.consumer_pos() <= self.producer_pos();
-        let ptr: *mut T = self.buf.as_mut_ptr().cast();
-        if contiguous {
-            let empty = from_raw_parts_mut(ptr, 0);
-            let len = self.producer_pos() - self.consumer_pos() + 1;
-            let buf = from_raw_parts_mut(ptr.add(self.consumer_pos()), len);
-            (buf, empty)
-        } else {
-            let p_idx = self.producer_idx();
-            let c_idx = self.consumer_idx();
-            let left = from_raw_parts_mut(ptr, p_idx);
-            let right = from_raw_parts_mut(ptr.add(c_idx), self.capacity() - c_idx);
-            (left, right)
+    /// Moves the consumer position `n` items after the claimed head `now`, this also clears
+    /// the busy bit. The position is only published by `commit_read` after
+    /// `set_defer_read(true)`.
+    fn release_head(&self, now: usize, n: usize) {
+        let pos = self.advance(now, n);
+        if self.policy == FullPolicy::DropOldest {
+            self.consumer_pos.store(pos, Ordering::Release);
+            return;
+        }
+
+        unsafe {
+            self.consumer_pos.set_local(pos);
+            self.consumer_pos.add_total(n);
+            if !self.defers_read() {
+                self.consumer_pos.store(pos, Ordering::Release);
+            }
+        }
+    }
+
+    /// The sequence number of the head item at `pos`.
+    unsafe fn seq_at(&self, pos: usize) -> u64 {
+        match &self.seqs {
+            Some(seqs) => seqs.get_unchecked(self.index(pos)).with(|ptr| ptr.read()),
+            // nothing is discarded then, and only the consumer moves its position.
+            None => self.consumer_pos.total(),
         }
     }
 }
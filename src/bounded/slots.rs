@@ -0,0 +1,225 @@
+use crate::bounded::ring::Indexer;
+use crate::bounded::{Receiver, Sender, Shared};
+use crate::loom::{AtomicPosition, Counter};
+use crate::wait::{AsyncWaker, WaitStrategy};
+use core::mem::MaybeUninit;
+use core::ops::{Deref, DerefMut};
+use core::ptr::NonNull;
+
+/// Free slots of a bounded channel reserved by [`Sender::reserve_slots`].
+///
+/// The slots can be filled in place, either one by one with [`push`] and [`write_with`], or
+/// all at once through [`as_mut_slices`]. Nothing is visible to the receiver until [`commit`],
+/// which publishes the written items with a single position update and one wake up.
+///
+/// Items that are written but not committed are dropped with the guard.
+///
+/// [`push`]: WriteSlots::push
+/// [`write_with`]: WriteSlots::write_with
+/// [`as_mut_slices`]: WriteSlots::as_mut_slices
+/// [`commit`]: WriteSlots::commit
+pub struct WriteSlots<'a, T, I: Indexer, W: WaitStrategy = AsyncWaker, C: Counter = AtomicPosition>
+{
+    /// Borrowed from the sender, which stays mutably borrowed by the guard.
+    shared: &'a Shared<T, I, W, C>,
+    start: usize,
+    len: usize,
+    written: usize,
+}
+
+impl<'a, T, I: Indexer, W: WaitStrategy, C: Counter> WriteSlots<'a, T, I, W, C> {
+    pub(super) fn new(sender: &'a mut Sender<'_, T, I, W, C>, len: usize) -> Self {
+        let shared = &*sender.inner;
+        Self {
+            shared,
+            start: shared.ring.write_pos(),
+            len,
+            written: 0,
+        }
+    }
+
+    /// The number of reserved slots.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The number of slots written from the front of the reservation.
+    pub fn written(&self) -> usize {
+        self.written
+    }
+
+    /// Returns all the reserved slots. The second slice is only non-empty when the
+    /// reservation wraps around the end of the ring.
+    ///
+    /// Slots written here are only taken into account after [`assume_written`].
+    ///
+    /// [`assume_written`]: WriteSlots::assume_written
+    pub fn as_mut_slices(&mut self) -> (&mut [MaybeUninit<T>], &mut [MaybeUninit<T>]) {
+        // Safety: the slots after the producer position are only touched by the producer,
+        // and we hold the mutable reference of the sender.
+        unsafe { self.shared.ring.slots_mut(self.start, self.len) }
+    }
+
+    /// Writes `item` into the next free slot, or returns it back if all the slots are written.
+    pub fn push(&mut self, item: T) -> Result<(), T> {
+        if self.written == self.len {
+            return Err(item);
+        }
+
+        unsafe {
+            self.write_with(|slot| {
+                slot.write(item);
+            });
+        }
+        Ok(())
+    }
+
+    /// Builds the next item in place.
+    ///
+    /// # Panics
+    ///
+    /// Panics if all the reserved slots are already written.
+    ///
+    /// # Safety
+    ///
+    /// `f` must initialize the slot.
+    pub unsafe fn write_with<F>(&mut self, f: F)
+    where
+        F: FnOnce(&mut MaybeUninit<T>),
+    {
+        assert!(self.written < self.len, "all reserved slots are written");
+
+        let ring = &self.shared.ring;
+        let (slot, _) = ring.slots_mut(ring.advance(self.start, self.written), 1);
+        f(&mut slot[0]);
+        self.written += 1;
+    }
+
+    /// Marks the first `n` reserved slots as written, after filling them
+    /// through [`as_mut_slices`].
+    ///
+    /// # Safety
+    ///
+    /// The first `n` slots must be initialized, and `n` must not exceed [`len`].
+    ///
+    /// [`as_mut_slices`]: WriteSlots::as_mut_slices
+    /// [`len`]: WriteSlots::len
+    pub unsafe fn assume_written(&mut self, n: usize) {
+        debug_assert!(n <= self.len);
+        self.written = n;
+    }
+
+    /// Publishes the first `n` written items to the receiver, and drops the rest.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `n` is bigger than [`written`].
+    ///
+    /// [`written`]: WriteSlots::written
+    pub fn commit(mut self, n: usize) {
+        assert!(n <= self.written, "commit more items than written");
+
+        if n > 0 {
+            unsafe {
+                self.shared.ring.publish(n);
+            }
+            self.shared.consumer.wake_by_ref();
+        }
+
+        // The remaining items now start right after the new producer position.
+        self.start = self.shared.ring.advance(self.start, n);
+        self.written -= n;
+    }
+}
+
+impl<T, I: Indexer, W: WaitStrategy, C: Counter> Drop for WriteSlots<'_, T, I, W, C> {
+    fn drop(&mut self) {
+        let written = self.written;
+        let (left, right) = unsafe { self.shared.ring.slots_mut(self.start, written) };
+        for slot in left.iter_mut().chain(right) {
+            unsafe { slot.assume_init_drop() };
+        }
+    }
+}
+
+/// A free slot of a bounded channel, returned by [`Sender::reserve`].
+///
+/// Sending through the permit can't fail. If the permit is dropped without sending,
+/// the slot is simply left free.
+pub struct Permit<'a, T, I: Indexer, W: WaitStrategy = AsyncWaker, C: Counter = AtomicPosition> {
+    shared: &'a Shared<T, I, W, C>,
+    /// `None` if the item will be discarded, see [`FullPolicy::DropNewest`].
+    ///
+    /// [`FullPolicy::DropNewest`]: crate::bounded::FullPolicy::DropNewest
+    slot: Option<usize>,
+}
+
+impl<'a, T, I: Indexer, W: WaitStrategy, C: Counter> Permit<'a, T, I, W, C> {
+    pub(super) fn new(sender: &'a mut Sender<'_, T, I, W, C>, slot: Option<usize>) -> Self {
+        Self {
+            shared: &sender.inner,
+            slot,
+        }
+    }
+
+    /// Writes `item` into the reserved slot, and notifies the receiver.
+    pub fn send(self, item: T) {
+        self.shared.send_at(item, self.slot);
+    }
+}
+
+/// The head item of a bounded channel, returned by [`Receiver::recv_ref`].
+///
+/// The item stays in the ring while the guard is alive, and is dropped in place
+/// when the guard is dropped, so large items never need to be moved out.
+pub struct RecvGuard<'a, T, I: Indexer, W: WaitStrategy = AsyncWaker, C: Counter = AtomicPosition> {
+    shared: &'a Shared<T, I, W, C>,
+    /// The slot of the head item, found once when the guard is created.
+    head: NonNull<T>,
+}
+
+impl<'a, T, I: Indexer, W: WaitStrategy, C: Counter> RecvGuard<'a, T, I, W, C> {
+    pub(super) fn new(receiver: &'a mut Receiver<'_, T, I, W, C>) -> Self {
+        debug_assert!(!receiver.inner.ring.is_empty());
+        let shared = &*receiver.inner;
+        // Safety: the guard is only created when the ring is not empty, and holds the
+        // receiver mutably, so the item can't be popped until the guard is dropped.
+        let head = unsafe { NonNull::from(&mut shared.ring.readable_slices_mut().0[0]) };
+        Self { shared, head }
+    }
+}
+
+// Safety: the guard is a `&mut T` of the head item, next to a `&Shared`.
+unsafe impl<T: Send, I: Indexer, W: WaitStrategy, C: Counter> Send for RecvGuard<'_, T, I, W, C> where
+    Shared<T, I, W, C>: Sync
+{
+}
+
+unsafe impl<T: Sync, I: Indexer, W: WaitStrategy, C: Counter> Sync for RecvGuard<'_, T, I, W, C> where
+    Shared<T, I, W, C>: Sync
+{
+}
+
+impl<T, I: Indexer, W: WaitStrategy, C: Counter> Deref for RecvGuard<'_, T, I, W, C> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe { self.head.as_ref() }
+    }
+}
+
+impl<T, I: Indexer, W: WaitStrategy, C: Counter> DerefMut for RecvGuard<'_, T, I, W, C> {
+    fn deref_mut(&mut self) -> &mut T {
+        unsafe { self.head.as_mut() }
+    }
+}
+
+impl<T, I: Indexer, W: WaitStrategy, C: Counter> Drop for RecvGuard<'_, T, I, W, C> {
+    fn drop(&mut self) {
+        self.shared.consume(1);
+    }
+}
@@ -1,23 +1,25 @@
 use crate::bounded::ring::Indexer;
 use crate::bounded::Sender;
 use crate::error::SendError;
+use crate::loom::{AtomicPosition, Counter};
+use crate::wait::{AsyncWaker, WaitStrategy};
+use core::pin::Pin;
+use core::task::{Context, Poll};
 use futures_sink::Sink;
-use std::pin::Pin;
-use std::task::{Context, Poll};
 
-pub struct SenderWrapper<T, I: Indexer> {
-    inner: Option<Sender<T, I>>,
+pub struct SenderWrapper<T, I: Indexer, W: WaitStrategy = AsyncWaker, C: Counter = AtomicPosition> {
+    inner: Option<Sender<'static, T, I, W, C>>,
 }
 
-impl<T, I: Indexer> SenderWrapper<T, I> {
-    pub fn new(sender: Sender<T, I>) -> Self {
+impl<T, I: Indexer, W: WaitStrategy, C: Counter> SenderWrapper<T, I, W, C> {
+    pub fn new(sender: Sender<'static, T, I, W, C>) -> Self {
         Self {
             inner: Some(sender),
         }
     }
 }
 
-impl<T, I: Indexer> Sink<T> for SenderWrapper<T, I> {
+impl<T, I: Indexer, W: WaitStrategy, C: Counter> Sink<T> for SenderWrapper<T, I, W, C> {
     type Error = SendError;
 
     fn poll_ready(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
@@ -30,7 +32,7 @@ impl<T, I: Indexer> Sink<T> for SenderWrapper<T, I> {
     fn start_send(mut self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
         self.inner
             .as_mut()
-            .map(|inner| inner.start_send(item))
+            .map(|inner| inner.start_send(item).map_err(|err| err.into_send_error()))
             .unwrap_or(Err(SendError::Disconnected))
     }
 
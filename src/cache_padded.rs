@@ -0,0 +1,30 @@
+use core::ops::Deref;
+
+/// Pads and aligns `T` to the length of a cache line, so it doesn't share its line with
+/// the fields written by the other side of a channel.
+///
+/// The prefetcher of x86_64 and the big cores of aarch64 pull cache lines in pairs,
+/// so 128 bytes are used there.
+#[cfg_attr(any(target_arch = "x86_64", target_arch = "aarch64"), repr(align(128)))]
+#[cfg_attr(
+    not(any(target_arch = "x86_64", target_arch = "aarch64")),
+    repr(align(64))
+)]
+#[derive(Debug, Default)]
+pub(crate) struct CachePadded<T> {
+    value: T,
+}
+
+impl<T> CachePadded<T> {
+    pub(crate) const fn new(value: T) -> Self {
+        Self { value }
+    }
+}
+
+impl<T> Deref for CachePadded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
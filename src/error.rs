@@ -1,4 +1,4 @@
-use std::fmt;
+use core::fmt;
 
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum SendError {
@@ -21,6 +21,7 @@ impl fmt::Display for SendError {
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for SendError {}
 
 impl SendError {
@@ -47,6 +48,7 @@ impl<T> fmt::Display for TrySendError<T> {
     }
 }
 
+#[cfg(feature = "std")]
 impl<T: core::any::Any> std::error::Error for TrySendError<T> {}
 
 impl<T> TrySendError<T> {
@@ -82,4 +84,5 @@ impl fmt::Display for TryRecvError {
     }
 }
 
+#[cfg(feature = "std")]
 impl std::error::Error for TryRecvError {}
@@ -0,0 +1,151 @@
+use crate::error::TryRecvError;
+use futures_util::Stream;
+use std::io;
+use std::ops::Deref;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+
+/// A receiver whose readiness is signaled through an eventfd, so it can be waited on with
+/// `epoll` or `mio` next to sockets, without an async runtime.
+///
+/// Works with both the bounded [`Receiver`] and the [`UnboundedReceiver`], and derefs to
+/// the inner receiver for the methods which only look at the channel. It doesn't deref
+/// mutably: receiving through the inner receiver would register another waker in place of
+/// the eventfd, which would then never become readable. The sender is not changed at all: the eventfd is written through the
+/// receiver's waker, so it is only written when the receiver has armed it.
+///
+/// The receiver is armed when [`try_recv`] returns `Err(TryRecvError::Empty)`, then the
+/// eventfd becomes readable once the sender sends an item or closes the channel. So the
+/// event loop should call [`try_recv`] until the channel is empty on every readiness event,
+/// which is also what edge triggered `mio` expects. The eventfd starts readable, so the
+/// first event arms it.
+///
+/// [`Receiver`]: crate::P2Receiver
+/// [`UnboundedReceiver`]: crate::UnboundedReceiver
+/// [`try_recv`]: EventfdReceiver::try_recv
+pub struct EventfdReceiver<R> {
+    inner: R,
+    event: Arc<EventFd>,
+    waker: Waker,
+}
+
+impl<R: Stream + Unpin> EventfdReceiver<R> {
+    pub fn new(inner: R) -> io::Result<Self> {
+        let event = Arc::new(EventFd::new()?);
+        Ok(Self {
+            inner,
+            waker: event.clone().into(),
+            event,
+        })
+    }
+
+    /// Receives an item without waiting, and arms the eventfd when the channel is empty.
+    pub fn try_recv(&mut self) -> Result<R::Item, TryRecvError> {
+        if let Poll::Ready(item) = self.poll_inner() {
+            return item.ok_or(TryRecvError::Disconnected);
+        }
+
+        // Reset the eventfd and check again, a wake up after the second registration
+        // writes the eventfd after it was cleared, so it can't be lost.
+        self.event.clear();
+        match self.poll_inner() {
+            Poll::Ready(item) => item.ok_or(TryRecvError::Disconnected),
+            Poll::Pending => Err(TryRecvError::Empty),
+        }
+    }
+
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    fn poll_inner(&mut self) -> Poll<Option<R::Item>> {
+        let mut cx = Context::from_waker(&self.waker);
+        Pin::new(&mut self.inner).poll_next(&mut cx)
+    }
+}
+
+impl<R> Deref for EventfdReceiver<R> {
+    type Target = R;
+
+    fn deref(&self) -> &R {
+        &self.inner
+    }
+}
+
+impl<R> AsFd for EventfdReceiver<R> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.event.fd.as_fd()
+    }
+}
+
+impl<R> AsRawFd for EventfdReceiver<R> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.event.fd.as_raw_fd()
+    }
+}
+
+#[cfg(feature = "mio")]
+impl<R> mio::event::Source for EventfdReceiver<R> {
+    fn register(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        mio::unix::SourceFd(&self.as_raw_fd()).register(registry, token, interests)
+    }
+
+    fn reregister(
+        &mut self,
+        registry: &mio::Registry,
+        token: mio::Token,
+        interests: mio::Interest,
+    ) -> io::Result<()> {
+        mio::unix::SourceFd(&self.as_raw_fd()).reregister(registry, token, interests)
+    }
+
+    fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()> {
+        mio::unix::SourceFd(&self.as_raw_fd()).deregister(registry)
+    }
+}
+
+struct EventFd {
+    fd: OwnedFd,
+}
+
+impl EventFd {
+    fn new() -> io::Result<Self> {
+        let fd = unsafe { libc::eventfd(1, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            fd: unsafe { OwnedFd::from_raw_fd(fd) },
+        })
+    }
+
+    fn clear(&self) {
+        let mut buf = 0u64;
+        // `EAGAIN` means it is already cleared.
+        unsafe {
+            libc::read(self.fd.as_raw_fd(), (&mut buf as *mut u64).cast(), 8);
+        }
+    }
+}
+
+impl Wake for EventFd {
+    fn wake(self: Arc<Self>) {
+        self.wake_by_ref()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        let buf = 1u64;
+        // The counter can only overflow after 2^64 - 2 wake ups without a read.
+        unsafe {
+            libc::write(self.fd.as_raw_fd(), (&buf as *const u64).cast(), 8);
+        }
+    }
+}
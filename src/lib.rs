@@ -33,22 +33,117 @@
 //! For [`unbounded`] channel, the underlying storage is a linked chunk queue, which is
 //! similar to `tokio`.
 //!
-//! For [`bounded`] channel, we use a ring buffer as the inner storage. So when you use [`bounded`]
-//! function to create a channel, the actual channel's size may be bigger then the number you passed in.
-//! Because ring buffer need it's size to be power of two, and also need reserve one slot. This behavior is
-//! the same as [`VecDeque`].
+//! For [`bounded`] channel, we use a ring buffer as the inner storage, and every slot of the ring holds an item.
+//! When you use [`bounded`] function to create a channel, the actual channel's size may be bigger then the number
+//! you passed in, because ring buffer need it's size to be power of two.
 //!
 //! If you don't want the requirement of power of tow, you can use [`exact_channel`] to create channel, which
-//! will not expand the underlying buffer's size to power of two, but will sacrifice a little performance.
+//! holds exactly the number of items you passed in, but will sacrifice a little performance. Both sides
+//! report the real size with `capacity`.
+//!
+//! On Linux, the `mirror` feature adds `mirrored_channel` and `mirrored_pipe`, whose ring is mapped twice
+//! back to back in memory. The items never wrap around there, so the readable and writable regions are always
+//! one contiguous slice.
 //!
 //! ## Batch operation
 //!
 //! Both send and receive support batch operation. You can use [`start_send`] to fill an item to the channel without
-//! notifying the receiver. When the channel is full, [`start_send`] will return `Err(TrySendErr::Full)`, and you
-//! should use [`flush`] to notify the receiver to consume messages.
+//! notifying the receiver. When the channel is full, [`start_send`] will return the item back with a `Full` error,
+//! and you should use [`flush`] to notify the receiver to consume messages. With [`set_batch_send`], the items are
+//! not even published before that, so a batch of [`start_send`] calls moves the shared position once.
+//!
+//! For many items, [`send_slice`] and [`send_iter`] fill the free space and publish the items all at once.
+//! If the items are expensive to move, [`reserve_slots`] gives out the free slots of the ring, so items
+//! can be built in place and published all at once.
 //!
 //! For the receiver, you can use [`try_recv`] to fetch an item from the channel. When the channel is empty, [`try_recv`]
 //! will return `Err(TryReceiveErr::Empty)`, and you should use [`want_recv`] to notify the sender to send more message.
+//! With [`set_batch_recv`], the slots of the received items are only released to the sender by [`want_recv`] or
+//! an explicit `commit`, so a batch of [`try_recv`] calls moves the shared position once.
+//!
+//! The items of the [`bounded`] channel can also be read where they sit in the ring. [`read_slices`] returns the
+//! readable items and [`consume`] pops them all at once, while [`recv_ref`] gives out the head item and pops it
+//! when the guard is dropped.
+//!
+//! ## Blocking operation
+//!
+//! Both channels can also be used from plain threads. [`blocking_send`] and [`blocking_recv`] park
+//! the calling thread until the operation can make progress, and `send_timeout` / `recv_timeout`
+//! give up after a while. One side can stay async while the other side blocks, no runtime is needed.
+//!
+//! Schedulers which are neither async runtimes nor threads, like a game loop or a GUI event queue, can give
+//! a callback to `set_notify` on either side of both channels. The callback fires where a waiting task would be
+//! woken, so the scheduler knows when to drain the channel again.
+//!
+//! ## Wait strategy
+//!
+//! By default, the waiting side of a [`bounded`] channel registers its waker, and the other side wakes it.
+//! [`channel_with_wait`] picks another [`WaitStrategy`] instead: a consumer pinned to a core can busy poll
+//! with [`BusySpin`] or [`SpinThenYield`], without any waker registration on either side, while [`SpinThenPark`]
+//! spins for a while before it falls back to the waker. On Linux, the `futex` feature adds `Futex`, which lets
+//! the blocking methods sleep on a futex that is only woken when they wait.
+//!
+//! ## Full policy
+//!
+//! By default, sending to a full [`bounded`] channel waits for the receiver. For streams where fresh data
+//! matters more than completeness, [`channel_with_policy`] can drop the newest or the oldest item instead,
+//! or fail right away. The discarded items are counted, and [`recv_seq`] returns the sequence number of
+//! each item, so the receiver can tell where the items are missing.
+//!
+//! ## Byte pipe
+//!
+//! [`pipe`] is a bounded channel of bytes, which copies the bytes in and out of the ring directly. With the
+//! `futures-io` or `tokio` feature, both halves implement the `AsyncRead` / `AsyncWrite` traits, so it can
+//! replace `tokio::io::duplex` when the bytes only flow in one direction.
+//!
+//! ## Variable-length records
+//!
+//! When the messages are byte records of very different sizes, [`bip_channel`] avoids one allocation per message.
+//! The sender reserves a contiguous record of the given length and writes it in place, and the receiver reads
+//! whole records as contiguous byte slices.
+//!
+//! ## Shared memory
+//!
+//! On Linux, the `shm` feature adds the `shm` module: a channel of plain data items in shared memory, which
+//! connects two processes. The memory is named with `shm_open`, or passed as a file descriptor, and both sides
+//! can block on a futex or wait asynchronously.
+//!
+//! ## Event loop readiness
+//!
+//! On Linux, the `eventfd` feature adds `EventfdReceiver`, which wraps either receiver and signals its readiness
+//! through an eventfd, so the channel can be registered with `epoll` next to sockets. With the `mio` feature,
+//! it is also a `mio::event::Source`.
+//!
+//! ## Remote channel
+//!
+//! On Unix, the `remote` feature adds the `remote` module, whose sender and receiver have the same methods as the
+//! bounded ones, but send `serde` items to another process through a Unix domain socket.
+//!
+//! ## Local channel
+//!
+//! When both sides live on the same thread, like the tasks of a tokio `LocalSet` or a wasm32 executor, the
+//! [`local`] module offers the same channels without any atomic operation. Its handles have the same methods
+//! as the other ones, except the blocking ones, and are neither `Send` nor `Sync`.
+//!
+//! ## Static and borrowed storage
+//!
+//! A [`StaticChannel`] lives in a `static` and is split once into `'static` halves, and [`channel_in`] builds a
+//! channel over a buffer of the caller, whose halves borrow it. Neither allocates, and their halves have the same
+//! methods as the ones of [`exact_channel`].
+//!
+//! ## Custom allocator
+//!
+//! [`channel_with_alloc`], [`exact_channel_with_alloc`] and [`unbounded_channel_with_alloc`] take the memory of
+//! the channel from an [`allocator::Allocator`]. On Linux, the `mmap` feature adds `MmapAllocator`, whose memory can
+//! be backed by huge pages, prefaulted and locked with `mlock`, so real-time threads never fault on the ring.
+//!
+//! ## `no_std`
+//!
+//! Without the default `std` feature, the crate only needs `core` and `alloc`, so the channels can be used
+//! with embedded executors like `embassy`. The blocking methods, `set_notify`, [`pipe`] and the OS specific
+//! features need `std`. On targets without native read-modify-write atomics, the `portable-atomic` feature
+//! takes the atomics and `Arc` from the `portable-atomic` crates instead, and the `u32-positions` feature
+//! keeps the positions of the bounded ring in 32 bits.
 //!
 //! ## `Stream` trait
 //!
@@ -60,25 +155,89 @@
 //! [`exact_channel`]: crate::exact_channel
 //! [`try_recv`]: crate::bounded::Receiver::try_recv
 //! [`start_send`]: crate::bounded::Sender::start_send
+//! [`set_batch_send`]: crate::bounded::Sender::set_batch_send
+//! [`set_batch_recv`]: crate::bounded::Receiver::set_batch_recv
 //! [`flush`]: crate::bounded::Sender::flush
+//! [`reserve_slots`]: crate::bounded::Sender::reserve_slots
+//! [`send_slice`]: crate::bounded::Sender::send_slice
+//! [`send_iter`]: crate::bounded::Sender::send_iter
 //! [`want_recv`]: crate::bounded::Receiver::want_recv
+//! [`read_slices`]: crate::bounded::Receiver::read_slices
+//! [`consume`]: crate::bounded::Receiver::consume
+//! [`recv_ref`]: crate::bounded::Receiver::recv_ref
+//! [`blocking_send`]: crate::bounded::Sender::blocking_send
+//! [`blocking_recv`]: crate::bounded::Receiver::blocking_recv
+//! [`channel_with_policy`]: crate::channel_with_policy
+//! [`channel_with_wait`]: crate::channel_with_wait
+//! [`recv_seq`]: crate::bounded::Receiver::recv_seq
+//! [`pipe`]: crate::pipe
+//! [`bip_channel`]: crate::bip_channel
 //! [`VecDeque`]: std::collections::VecDeque
 
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
 #[macro_use]
 mod loom;
 
 mod atomic_waker;
 
+mod cache_padded;
+
+#[cfg(feature = "std")]
+mod blocking;
+
+#[cfg(feature = "std")]
+mod notify;
+
+mod wait;
+#[cfg(all(target_os = "linux", feature = "futex", not(loom)))]
+pub use self::wait::Futex;
+#[cfg(feature = "std")]
+pub use self::wait::SpinThenYield;
+pub use self::wait::{AsyncWaker, BusySpin, SpinThenPark, WaitStrategy};
+
 pub mod error;
 
+pub mod allocator;
+
 mod bounded;
 
 pub use self::bounded::{
-    channel, exact_channel, wrapper::SenderWrapper, ExactReceiver, ExactSender, P2Receiver,
-    P2Sender,
+    bip_channel, channel, channel_with_policy, channel_with_wait, exact_channel,
+    exact_channel_with_policy, exact_channel_with_wait, wrapper::SenderWrapper, BipReceiver,
+    BipSender, ExactReceiver, ExactSender, FullPolicy, P2Receiver, P2Sender, Permit, RecordGuard,
+    RecordSlot, RecvGuard, WriteSlots,
+};
+
+#[cfg(not(loom))]
+pub use self::bounded::{
+    channel_in, channel_with_alloc, exact_channel_with_alloc, ChannelIn, ScopedReceiver,
+    ScopedSender, StaticChannel,
 };
 
+#[cfg(feature = "std")]
+pub use self::bounded::{pipe, PipeReader, PipeWriter};
+
+#[cfg(all(target_os = "linux", feature = "mirror", not(loom)))]
+pub use self::bounded::{mirrored_channel, mirrored_pipe};
+
+#[cfg(all(target_os = "linux", feature = "shm", not(loom)))]
+pub mod shm;
+
+#[cfg(all(target_os = "linux", feature = "eventfd", not(loom)))]
+mod eventfd;
+#[cfg(all(target_os = "linux", feature = "eventfd", not(loom)))]
+pub use self::eventfd::EventfdReceiver;
+
+#[cfg(all(unix, feature = "remote", not(loom)))]
+pub mod remote;
+
+pub mod local;
+
 mod unbounded;
 pub use self::unbounded::{
-    unbounded_channel, wrapper::UnboundedSenderWrapper, UnboundedReceiver, UnboundedSender,
+    unbounded_channel, unbounded_channel_with_alloc, wrapper::UnboundedSenderWrapper,
+    UnboundedReceiver, UnboundedSender,
 };
@@ -0,0 +1,109 @@
+//! Channels whose both sides live on the same thread, like the tasks of a tokio `LocalSet`
+//! or a wasm32 executor.
+//!
+//! [`channel`], [`exact_channel`] and [`unbounded_channel`] are the same channels as the
+//! other ones, but the shared state is kept in an `Rc`, the positions and the closed flag in
+//! `Cell`s and the wakers in a [`LocalWaker`]. Only the link between the blocks of the
+//! unbounded channel is still an atomic pointer, which is touched once per block. The
+//! handles are neither `Send` nor `Sync`.
+//!
+//! The handles have the same methods as the bounded [`Sender`] / [`Receiver`] and the
+//! [`UnboundedSender`] / [`UnboundedReceiver`], except the blocking receiving ones and
+//! those of the bounded sender: the other side can only make progress on this thread, so
+//! they could only deadlock.
+//!
+//! ```
+//! # async fn run() {
+//! let (mut tx, mut rx) = spsc_rs::local::channel(16);
+//!
+//! tx.send(1).await.unwrap();
+//! assert_eq!(rx.recv().await, Some(1));
+//! # }
+//! ```
+//!
+//! [`Sender`]: crate::P2Sender
+//! [`Receiver`]: crate::P2Receiver
+//! [`UnboundedSender`]: crate::UnboundedSender
+//! [`UnboundedReceiver`]: crate::UnboundedReceiver
+
+use crate::bounded::ring::{And, ExactRing, P2Ring, Remainder};
+use crate::bounded::{self, from_ring, FullPolicy};
+use crate::unbounded::{self, from_queue, queue::Queue};
+use crate::wait::WaitStrategy;
+use core::cell::Cell;
+use core::fmt;
+use core::task::Waker;
+
+/// The waker of one side, the `AtomicWaker` without the atomics.
+#[derive(Default)]
+pub struct LocalWaker {
+    waker: Cell<Option<Waker>>,
+}
+
+impl WaitStrategy for LocalWaker {
+    fn register(&self, waker: &Waker) {
+        let waker = match self.waker.take() {
+            Some(old) if old.will_wake(waker) => old,
+            _ => waker.clone(),
+        };
+        self.waker.set(Some(waker));
+    }
+
+    /// Wakes the registered waker once, like `AtomicWaker::wake_by_ref`.
+    fn wake_by_ref(&self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+impl Clone for LocalWaker {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Debug for LocalWaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("LocalWaker")
+    }
+}
+
+pub type Sender<T, I> = bounded::Sender<'static, T, I, LocalWaker, Cell<usize>>;
+pub type Receiver<T, I> = bounded::Receiver<'static, T, I, LocalWaker, Cell<usize>>;
+pub type WriteSlots<'a, T, I> = bounded::WriteSlots<'a, T, I, LocalWaker, Cell<usize>>;
+pub type Permit<'a, T, I> = bounded::Permit<'a, T, I, LocalWaker, Cell<usize>>;
+pub type RecvGuard<'a, T, I> = bounded::RecvGuard<'a, T, I, LocalWaker, Cell<usize>>;
+pub type SenderWrapper<T, I> = bounded::wrapper::SenderWrapper<T, I, LocalWaker, Cell<usize>>;
+
+pub type P2Sender<T> = Sender<T, And>;
+pub type P2Receiver<T> = Receiver<T, And>;
+
+/// The local version of [`crate::channel`].
+pub fn channel<T>(size: usize) -> (P2Sender<T>, P2Receiver<T>) {
+    from_ring(
+        P2Ring::with_capacity(size, FullPolicy::Wait),
+        LocalWaker::default(),
+    )
+}
+
+pub type ExactSender<T> = Sender<T, Remainder>;
+pub type ExactReceiver<T> = Receiver<T, Remainder>;
+
+/// The local version of [`crate::exact_channel`].
+pub fn exact_channel<T>(size: usize) -> (ExactSender<T>, ExactReceiver<T>) {
+    from_ring(
+        ExactRing::with_capacity(size, FullPolicy::Wait),
+        LocalWaker::default(),
+    )
+}
+
+pub type UnboundedSender<T> = unbounded::UnboundedSender<T, LocalWaker, Cell<usize>>;
+pub type UnboundedReceiver<T> = unbounded::UnboundedReceiver<T, LocalWaker, Cell<usize>>;
+pub type UnboundedSenderWrapper<T> =
+    unbounded::wrapper::UnboundedSenderWrapper<T, LocalWaker, Cell<usize>>;
+
+/// The local version of [`crate::unbounded_channel`].
+pub fn unbounded_channel<T>() -> (UnboundedSender<T>, UnboundedReceiver<T>) {
+    from_queue(Queue::new())
+}
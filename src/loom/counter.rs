@@ -0,0 +1,187 @@
+use super::{Arc, AtomicU32, AtomicUsize, Ordering};
+use alloc::rc::Rc;
+use core::cell::Cell;
+use core::ops::Deref;
+
+/// A position or counter shared by the two sides of a ring or a queue.
+///
+/// The channels use atomics, while the `local` channels, whose both sides live on the
+/// same thread, use a plain `Cell` and ignore the orderings.
+pub trait Counter: Default {
+    /// The number of bits kept by the counter, the values wrap around beyond them.
+    const BITS: u32;
+
+    /// How the two halves of a channel hold the state they share, which goes together
+    /// with its counters: an `Arc` for the atomics, an `Rc` for the `Cell`.
+    type Ptr<S>: Deref<Target = S> + Clone + Unpin;
+
+    fn share<S>(shared: S) -> Self::Ptr<S>;
+
+    fn load(&self, order: Ordering) -> usize;
+
+    fn store(&self, val: usize, order: Ordering);
+
+    fn compare_exchange(
+        &self,
+        current: usize,
+        new: usize,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<usize, usize>;
+
+    fn compare_exchange_weak(
+        &self,
+        current: usize,
+        new: usize,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<usize, usize>;
+
+    fn fetch_add(&self, val: usize, order: Ordering) -> usize;
+}
+
+/// The atomic type of the positions of the bounded ring.
+#[cfg(not(feature = "u32-positions"))]
+pub(crate) type AtomicPosition = AtomicUsize;
+
+/// The atomic type of the positions of the bounded ring.
+#[cfg(feature = "u32-positions")]
+pub(crate) type AtomicPosition = AtomicU32;
+
+impl Counter for AtomicUsize {
+    const BITS: u32 = usize::BITS;
+
+    type Ptr<S> = Arc<S>;
+
+    fn share<S>(shared: S) -> Self::Ptr<S> {
+        Arc::new(shared)
+    }
+
+    fn load(&self, order: Ordering) -> usize {
+        AtomicUsize::load(self, order)
+    }
+
+    fn store(&self, val: usize, order: Ordering) {
+        AtomicUsize::store(self, val, order)
+    }
+
+    fn compare_exchange(
+        &self,
+        current: usize,
+        new: usize,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<usize, usize> {
+        AtomicUsize::compare_exchange(self, current, new, success, failure)
+    }
+
+    fn compare_exchange_weak(
+        &self,
+        current: usize,
+        new: usize,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<usize, usize> {
+        AtomicUsize::compare_exchange_weak(self, current, new, success, failure)
+    }
+
+    fn fetch_add(&self, val: usize, order: Ordering) -> usize {
+        AtomicUsize::fetch_add(self, val, order)
+    }
+}
+
+impl Counter for AtomicU32 {
+    const BITS: u32 = u32::BITS;
+
+    type Ptr<S> = Arc<S>;
+
+    fn share<S>(shared: S) -> Self::Ptr<S> {
+        Arc::new(shared)
+    }
+
+    fn load(&self, order: Ordering) -> usize {
+        AtomicU32::load(self, order) as usize
+    }
+
+    fn store(&self, val: usize, order: Ordering) {
+        AtomicU32::store(self, val as u32, order)
+    }
+
+    fn compare_exchange(
+        &self,
+        current: usize,
+        new: usize,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<usize, usize> {
+        AtomicU32::compare_exchange(self, current as u32, new as u32, success, failure)
+            .map(|val| val as usize)
+            .map_err(|val| val as usize)
+    }
+
+    fn compare_exchange_weak(
+        &self,
+        current: usize,
+        new: usize,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<usize, usize> {
+        AtomicU32::compare_exchange_weak(self, current as u32, new as u32, success, failure)
+            .map(|val| val as usize)
+            .map_err(|val| val as usize)
+    }
+
+    fn fetch_add(&self, val: usize, order: Ordering) -> usize {
+        AtomicU32::fetch_add(self, val as u32, order) as usize
+    }
+}
+
+impl Counter for Cell<usize> {
+    const BITS: u32 = usize::BITS;
+
+    type Ptr<S> = Rc<S>;
+
+    fn share<S>(shared: S) -> Self::Ptr<S> {
+        Rc::new(shared)
+    }
+
+    fn load(&self, _: Ordering) -> usize {
+        self.get()
+    }
+
+    fn store(&self, val: usize, _: Ordering) {
+        self.set(val)
+    }
+
+    fn compare_exchange(
+        &self,
+        current: usize,
+        new: usize,
+        _: Ordering,
+        _: Ordering,
+    ) -> Result<usize, usize> {
+        let actual = self.get();
+        if actual == current {
+            self.set(new);
+            Ok(actual)
+        } else {
+            Err(actual)
+        }
+    }
+
+    fn compare_exchange_weak(
+        &self,
+        current: usize,
+        new: usize,
+        success: Ordering,
+        failure: Ordering,
+    ) -> Result<usize, usize> {
+        Counter::compare_exchange(self, current, new, success, failure)
+    }
+
+    fn fetch_add(&self, val: usize, _: Ordering) -> usize {
+        let old = self.get();
+        self.set(old.wrapping_add(val));
+        old
+    }
+}
@@ -1,12 +1,16 @@
+#[cfg(all(not(loom), not(feature = "portable-atomic")))]
+pub(crate) use core::sync::atomic::*;
 #[cfg(loom)]
 pub(crate) use loom::sync::atomic::*;
-#[cfg(not(loom))]
-pub(crate) use std::sync::atomic::*;
+#[cfg(all(not(loom), feature = "portable-atomic"))]
+pub(crate) use portable_atomic::*;
 
+#[cfg(all(not(loom), not(feature = "portable-atomic")))]
+pub(crate) use alloc::sync::Arc;
 #[cfg(loom)]
 pub(crate) use loom::sync::Arc;
-#[cfg(not(loom))]
-pub(crate) use std::sync::Arc;
+#[cfg(all(not(loom), feature = "portable-atomic"))]
+pub(crate) use portable_atomic_util::Arc;
 
 #[cfg(loom)]
 pub(crate) use loom::cell::UnsafeCell;
@@ -17,9 +21,12 @@ mod cell;
 #[cfg(not(loom))]
 pub(crate) use self::cell::UnsafeCell;
 
+mod counter;
+pub(crate) use self::counter::{AtomicPosition, Counter};
+
 #[cfg(loom)]
 pub(crate) use loom::thread;
-#[cfg(not(loom))]
+#[cfg(all(not(loom), feature = "std"))]
 pub(crate) use std::thread;
 
 macro_rules! if_loom {
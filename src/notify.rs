@@ -0,0 +1,19 @@
+use std::sync::Arc;
+use std::task::{Wake, Waker};
+
+/// Turns a `set_notify` callback into a waker, so it can be registered like a task.
+pub(crate) fn waker(notify: Arc<dyn Fn() + Send + Sync>) -> Waker {
+    Arc::new(NotifyWaker(notify)).into()
+}
+
+struct NotifyWaker(Arc<dyn Fn() + Send + Sync>);
+
+impl Wake for NotifyWaker {
+    fn wake(self: Arc<Self>) {
+        (self.0)()
+    }
+
+    fn wake_by_ref(self: &Arc<Self>) {
+        (self.0)()
+    }
+}
@@ -0,0 +1,464 @@
+//! A typed channel between two processes, over a Unix domain socket.
+//!
+//! [`RemoteSender`] and [`RemoteReceiver`] have the same methods as the bounded [`Sender`]
+//! and [`Receiver`], so code written against the in-process channel can be pointed at
+//! another process. The items are encoded with `bincode`, and sent as frames prefixed by
+//! their length in a little endian `u32`. The receiver refuses the frames longer than
+//! [`DEFAULT_MAX_FRAME_LEN`], or the limit given to [`RemoteReceiver::with_max_frame_len`].
+//!
+//! Each side buffers up to `size` encoded frames in a bounded channel, and a helper thread
+//! moves the frames between that channel and the socket. So a full socket gives the same
+//! back pressure as a full channel, and [`RemoteSender::flush`] waits until the frames are
+//! written to the socket.
+//!
+//! ```no_run
+//! # async fn run() -> std::io::Result<()> {
+//! use spsc_rs::remote::{RemoteReceiver, RemoteSender};
+//! use std::os::unix::net::UnixStream;
+//!
+//! let (a, b) = UnixStream::pair()?;
+//! // usually `b` is passed to a child process, which makes the receiver from it.
+//! let mut tx = RemoteSender::<String>::new(a, 16)?;
+//! let mut rx = RemoteReceiver::<String>::new(b, 16)?;
+//!
+//! tx.send("hello".to_string()).await.unwrap();
+//! assert_eq!(rx.recv().await.as_deref(), Some("hello"));
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Only available on Unix with the `remote` feature.
+//!
+//! [`Sender`]: crate::P2Sender
+//! [`Receiver`]: crate::P2Receiver
+
+use crate::blocking;
+use crate::bounded::{channel, P2Receiver, P2Sender};
+use crate::error::{SendError, TryRecvError, TrySendError};
+use futures_sink::Sink;
+use futures_util::future::poll_fn;
+use futures_util::Stream;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::marker::PhantomData;
+use std::net::Shutdown;
+use std::os::unix::net::UnixStream;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::Duration;
+
+/// The longest frame a receiver accepts by default, 16 MiB.
+pub const DEFAULT_MAX_FRAME_LEN: usize = 16 << 20;
+
+/// Creates a connected pair over a new socketpair, mostly useful for testing.
+pub fn channel_pair<T>(size: usize) -> io::Result<(RemoteSender<T>, RemoteReceiver<T>)>
+where
+    T: Serialize + DeserializeOwned,
+{
+    let (a, b) = UnixStream::pair()?;
+    Ok((RemoteSender::new(a, size)?, RemoteReceiver::new(b, size)?))
+}
+
+/// The sending half of a remote channel.
+///
+/// Dropping the sender or calling [`close`] closes the channel: the buffered frames are still
+/// written, then the write side of the socket is shut down, so the receiver sees the end of
+/// the stream.
+///
+/// [`close`]: RemoteSender::close
+pub struct RemoteSender<T> {
+    inner: P2Sender<Vec<u8>>,
+    _marker: PhantomData<fn(T)>,
+}
+
+impl<T: Serialize> RemoteSender<T> {
+    /// Sends the items of this process through `stream`, buffering up to `size` frames.
+    pub fn new(stream: UnixStream, size: usize) -> io::Result<Self> {
+        stream.set_nonblocking(false)?;
+        let (tx, rx) = channel(size);
+        thread::Builder::new()
+            .name("spsc-rs-remote-tx".into())
+            .spawn(move || write_frames(rx, stream))?;
+
+        Ok(Self {
+            inner: tx,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Puts an item into the channel without notifying the writer thread, use [`flush`]
+    /// to notify it once a batch of items is sent.
+    ///
+    /// If the item can't be encoded, [`RemoteSendError::Encode`] is returned with the item,
+    /// the same for every method which sends an item.
+    ///
+    /// [`flush`]: RemoteSender::flush
+    pub fn start_send(&mut self, item: T) -> Result<(), RemoteSendError<T>> {
+        let (frame, item) = encode(item)?;
+        self.inner
+            .start_send(frame)
+            .map_err(|err| with_item(err, item))
+    }
+
+    pub fn try_send(&mut self, item: T) -> Result<(), RemoteSendError<T>> {
+        let (frame, item) = encode(item)?;
+        self.inner
+            .try_send(frame)
+            .map_err(|err| with_item(err, item))
+    }
+
+    pub async fn send(&mut self, item: T) -> Result<(), RemoteSendError<T>> {
+        let (frame, item) = encode(item)?;
+        self.inner
+            .send(frame)
+            .await
+            .map_err(|err| with_item(err, item))
+    }
+
+    pub fn blocking_send(&mut self, item: T) -> Result<(), RemoteSendError<T>> {
+        let (frame, item) = encode(item)?;
+        self.inner
+            .blocking_send(frame)
+            .map_err(|err| with_item(err, item))
+    }
+
+    pub fn send_timeout(&mut self, item: T, timeout: Duration) -> Result<(), RemoteSendError<T>> {
+        let (frame, item) = encode(item)?;
+        self.inner
+            .send_timeout(frame, timeout)
+            .map_err(|err| with_item(err, item))
+    }
+
+    pub fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), SendError>> {
+        self.inner.poll_ready(cx)
+    }
+
+    /// Waits until all the sent items are written to the socket.
+    pub fn poll_flush(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), SendError>> {
+        self.inner.poll_flush(cx)
+    }
+
+    pub async fn flush(&mut self) -> Result<(), SendError> {
+        poll_fn(|cx| self.poll_flush(cx)).await
+    }
+
+    /// Returns whether this channel is closed.
+    ///
+    /// The writer thread only finds out the receiver is gone when a write fails, so
+    /// the channel may look open until the next items are sent.
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// Closes the channel without waiting, like dropping the sender.
+    ///
+    /// The buffered frames are still written by the writer thread, use [`flush`] before
+    /// to wait for them.
+    ///
+    /// [`flush`]: RemoteSender::flush
+    pub fn close(&mut self) {
+        self.inner.close();
+    }
+}
+
+/// The errors of the sink don't give the item back.
+impl<T: Serialize> Sink<T> for RemoteSender<T> {
+    type Error = RemoteSendError<()>;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_ready(cx).map_err(Into::into)
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: T) -> Result<(), Self::Error> {
+        self.get_mut().start_send(item).map_err(|err| err.map(drop))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_flush(cx).map_err(Into::into)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        if this.inner.is_closed() {
+            return Poll::Ready(Ok(()));
+        }
+
+        futures_util::ready!(this.poll_flush(cx))?;
+        this.close();
+        Poll::Ready(Ok(()))
+    }
+}
+
+/// The receiving half of a remote channel.
+pub struct RemoteReceiver<T> {
+    inner: P2Receiver<Vec<u8>>,
+    /// Used to stop the reader thread, which is blocked on the socket.
+    stream: UnixStream,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: DeserializeOwned> RemoteReceiver<T> {
+    /// Receives the items sent through `stream`, buffering up to `size` frames.
+    pub fn new(stream: UnixStream, size: usize) -> io::Result<Self> {
+        Self::with_max_frame_len(stream, size, DEFAULT_MAX_FRAME_LEN)
+    }
+
+    /// The same as [`new`], but frames longer than `max_frame_len` close the channel
+    /// instead of [`DEFAULT_MAX_FRAME_LEN`].
+    ///
+    /// The length prefix comes from the other process, so the limit keeps a broken or
+    /// hostile sender from making the reader thread allocate up to 4 GiB per frame.
+    ///
+    /// [`new`]: RemoteReceiver::new
+    pub fn with_max_frame_len(
+        stream: UnixStream,
+        size: usize,
+        max_frame_len: usize,
+    ) -> io::Result<Self> {
+        stream.set_nonblocking(false)?;
+        let (tx, rx) = channel(size);
+        let reader = stream.try_clone()?;
+        thread::Builder::new()
+            .name("spsc-rs-remote-rx".into())
+            .spawn(move || read_frames(tx, reader, max_frame_len))?;
+
+        Ok(Self {
+            inner: rx,
+            stream,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Receives an item without waiting.
+    ///
+    /// A frame which can't be decoded means the other side is broken, so the channel
+    /// is closed and `Disconnected` is returned, the same for every method which
+    /// receives an item.
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        let frame = self.inner.try_recv()?;
+        self.decode(frame).ok_or(TryRecvError::Disconnected)
+    }
+
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        self.inner
+            .poll_recv(cx)
+            .map(|frame| frame.and_then(|frame| self.decode(frame)))
+    }
+
+    pub async fn recv(&mut self) -> Option<T> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    pub fn blocking_recv(&mut self) -> Option<T> {
+        blocking::block_on(|cx| self.poll_recv(cx))
+    }
+
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, TryRecvError> {
+        let frame = self.inner.recv_timeout(timeout)?;
+        self.decode(frame).ok_or(TryRecvError::Disconnected)
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.inner.is_closed()
+    }
+
+    /// Closes the channel, and shuts down the read side of the socket, so the sender
+    /// fails on its next write.
+    pub fn close(&mut self) {
+        self.inner.close();
+        let _ = self.stream.shutdown(Shutdown::Read);
+    }
+
+    fn decode(&mut self, frame: Vec<u8>) -> Option<T> {
+        let item = bincode::deserialize(&frame).ok();
+        if item.is_none() {
+            self.close();
+        }
+        item
+    }
+}
+
+impl<T> Drop for RemoteReceiver<T> {
+    fn drop(&mut self) {
+        // the channel is closed when `inner` is dropped.
+        let _ = self.stream.shutdown(Shutdown::Read);
+    }
+}
+
+impl<T: DeserializeOwned> Stream for RemoteReceiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+/// The error of the sending methods of [`RemoteSender`], which gives the item back.
+///
+/// Besides the errors of the in-process channel, the item may not be encodable.
+#[derive(Clone, PartialEq, Eq)]
+pub enum RemoteSendError<T> {
+    /// The channel is full, only returned by the methods which don't wait.
+    Full(T),
+    Disconnected(T),
+    /// `bincode` can't encode the item, or its frame is longer than a `u32` can tell.
+    Encode(T),
+}
+
+impl<T> RemoteSendError<T> {
+    pub fn is_full(&self) -> bool {
+        matches!(self, RemoteSendError::Full(_))
+    }
+
+    pub fn is_disconnected(&self) -> bool {
+        matches!(self, RemoteSendError::Disconnected(_))
+    }
+
+    pub fn is_encode(&self) -> bool {
+        matches!(self, RemoteSendError::Encode(_))
+    }
+
+    pub fn into_inner(self) -> T {
+        match self {
+            RemoteSendError::Full(item)
+            | RemoteSendError::Disconnected(item)
+            | RemoteSendError::Encode(item) => item,
+        }
+    }
+
+    fn map<U>(self, f: impl FnOnce(T) -> U) -> RemoteSendError<U> {
+        match self {
+            RemoteSendError::Full(item) => RemoteSendError::Full(f(item)),
+            RemoteSendError::Disconnected(item) => RemoteSendError::Disconnected(f(item)),
+            RemoteSendError::Encode(item) => RemoteSendError::Encode(f(item)),
+        }
+    }
+}
+
+impl From<SendError> for RemoteSendError<()> {
+    fn from(err: SendError) -> Self {
+        match err {
+            SendError::Full => RemoteSendError::Full(()),
+            SendError::Disconnected => RemoteSendError::Disconnected(()),
+        }
+    }
+}
+
+impl<T> fmt::Debug for RemoteSendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self {
+            RemoteSendError::Full(_) => "Full",
+            RemoteSendError::Disconnected(_) => "Disconnected",
+            RemoteSendError::Encode(_) => "Encode",
+        };
+        f.debug_struct("RemoteSendError")
+            .field("kind", &format_args!("{}", kind))
+            .finish()
+    }
+}
+
+impl<T> fmt::Display for RemoteSendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RemoteSendError::Full(_) => SendError::Full.fmt(f),
+            RemoteSendError::Disconnected(_) => SendError::Disconnected.fmt(f),
+            RemoteSendError::Encode(_) => {
+                write!(f, "send failed because the item can't be encoded")
+            }
+        }
+    }
+}
+
+impl<T> std::error::Error for RemoteSendError<T> {}
+
+/// Returns the frame along with the item, which is given back if sending the frame fails.
+fn encode<T: Serialize>(item: T) -> Result<(Vec<u8>, T), RemoteSendError<T>> {
+    match bincode::serialize(&item) {
+        Ok(frame) if frame.len() <= u32::MAX as usize => Ok((frame, item)),
+        _ => Err(RemoteSendError::Encode(item)),
+    }
+}
+
+fn with_item<T>(err: TrySendError<Vec<u8>>, item: T) -> RemoteSendError<T> {
+    match err.into_send_error() {
+        SendError::Full => RemoteSendError::Full(item),
+        SendError::Disconnected => RemoteSendError::Disconnected(item),
+    }
+}
+
+/// Writes the frames until the sender is dropped, or the socket fails.
+///
+/// The frames are only popped after they are written, so `flush` on the sender waits
+/// for the socket.
+fn write_frames(mut rx: P2Receiver<Vec<u8>>, stream: UnixStream) {
+    let mut writer = BufWriter::new(&stream);
+    while blocking::block_on(|cx| rx.poll_readable(cx)) {
+        let (left, right) = rx.read_slices();
+        let n = left.len() + right.len();
+
+        let res = left
+            .iter()
+            .chain(right)
+            .try_for_each(|frame| write_frame(&mut writer, frame))
+            .and_then(|_| writer.flush());
+        if res.is_err() {
+            // dropping `rx` closes the channel, so the sender sees the error.
+            return;
+        }
+        rx.consume(n);
+    }
+
+    let _ = stream.shutdown(Shutdown::Write);
+}
+
+fn write_frame(writer: &mut impl Write, frame: &[u8]) -> io::Result<()> {
+    writer.write_all(&(frame.len() as u32).to_le_bytes())?;
+    writer.write_all(frame)
+}
+
+/// Reads the frames until the end of the stream, or the receiver is closed.
+fn read_frames(mut tx: P2Sender<Vec<u8>>, stream: UnixStream, max_frame_len: usize) {
+    let mut reader = BufReader::new(stream);
+    loop {
+        match read_frame(&mut reader, max_frame_len) {
+            Ok(Some(frame)) => {
+                if tx.blocking_send(frame).is_err() {
+                    return;
+                }
+            }
+            Ok(None) => return,
+            Err(_) => {
+                // the other side is broken, so the sender fails on its next write, like
+                // with a frame which can't be decoded.
+                let _ = reader.get_ref().shutdown(Shutdown::Read);
+                return;
+            }
+        }
+    }
+}
+
+/// Returns `None` at the end of the stream, or `InvalidData` if the frame is longer
+/// than `max_frame_len`.
+fn read_frame(reader: &mut impl Read, max_frame_len: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(err) => return Err(err),
+    }
+
+    let len = u32::from_le_bytes(len) as usize;
+    if len > max_frame_len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the frame is too long",
+        ));
+    }
+
+    let mut frame = vec![0; len];
+    reader.read_exact(&mut frame)?;
+    Ok(Some(frame))
+}
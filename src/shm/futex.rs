@@ -0,0 +1,161 @@
+use crate::atomic_waker::AtomicWaker;
+use std::sync::atomic::{fence, AtomicBool, AtomicU32, Ordering};
+use std::sync::Arc;
+use std::thread::{self, Thread};
+use std::time::{Duration, Instant};
+use std::{io, ptr};
+
+/// A futex word which is bumped when a position moves while the other side is waiting,
+/// together with the number of waiters, so the syscall is skipped when nobody waits.
+#[repr(C)]
+#[derive(Default)]
+pub(crate) struct Futex {
+    seq: AtomicU32,
+    waiters: AtomicU32,
+}
+
+impl Futex {
+    /// Wakes up all the waiters. The new position must be stored before.
+    pub(crate) fn notify(&self) {
+        // Pairs with the fence in `wait`: either the waiter sees the new position,
+        // or we see the waiter.
+        fence(Ordering::SeqCst);
+        if self.waiters.load(Ordering::Relaxed) > 0 {
+            self.seq.fetch_add(1, Ordering::Release);
+            futex_wake(&self.seq);
+        }
+    }
+
+    /// Waits until `ready` returns `true`, or the deadline is reached.
+    /// Returns the last result of `ready`.
+    pub(crate) fn wait(&self, deadline: Option<Instant>, ready: impl Fn() -> bool) -> bool {
+        loop {
+            if ready() {
+                return true;
+            }
+
+            self.waiters.fetch_add(1, Ordering::SeqCst);
+            fence(Ordering::SeqCst);
+            let seq = self.seq.load(Ordering::Acquire);
+
+            let timeout = match deadline {
+                None => None,
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(timeout) => Some(timeout),
+                    None => {
+                        self.waiters.fetch_sub(1, Ordering::Relaxed);
+                        return ready();
+                    }
+                },
+            };
+
+            if !ready() {
+                futex_wait(&self.seq, seq, timeout);
+            }
+            self.waiters.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    /// Wakes up the waiters of this process without moving any position, so they can
+    /// check their own condition.
+    fn interrupt(&self) {
+        self.seq.fetch_add(1, Ordering::Release);
+        futex_wake(&self.seq);
+    }
+}
+
+fn futex_wait(word: &AtomicU32, val: u32, timeout: Option<Duration>) {
+    let ts = timeout.map(|timeout| libc::timespec {
+        tv_sec: timeout.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+        tv_nsec: timeout.subsec_nanos() as libc::c_long,
+    });
+    let ts = ts
+        .as_ref()
+        .map_or(ptr::null(), |ts| ts as *const libc::timespec);
+
+    // Not `FUTEX_PRIVATE_FLAG`, the word is shared with another process.
+    // Spurious wake ups, `EAGAIN` and `EINTR` are all handled by the caller's loop.
+    unsafe {
+        libc::syscall(libc::SYS_futex, word.as_ptr(), libc::FUTEX_WAIT, val, ts);
+    }
+}
+
+fn futex_wake(word: &AtomicU32) {
+    unsafe {
+        libc::syscall(libc::SYS_futex, word.as_ptr(), libc::FUTEX_WAKE, i32::MAX);
+    }
+}
+
+/// Drives the async readiness of one side: the futex can't be polled, so a helper thread
+/// waits on it when a task asks for it, and wakes the task through an `AtomicWaker`.
+pub(crate) struct AsyncWaiter {
+    shared: Arc<WaiterShared>,
+    thread: Thread,
+}
+
+struct WaiterShared {
+    waker: AtomicWaker,
+    armed: AtomicBool,
+    stopped: AtomicBool,
+}
+
+impl AsyncWaiter {
+    /// `futex` must point into a mapping which is kept alive by `ready`.
+    pub(crate) fn spawn<F>(futex: *const Futex, ready: F) -> io::Result<Self>
+    where
+        F: Fn() -> bool + Send + 'static,
+    {
+        let shared = Arc::new(WaiterShared {
+            waker: Default::default(),
+            armed: AtomicBool::new(false),
+            stopped: AtomicBool::new(false),
+        });
+
+        let futex = FutexPtr(futex);
+        let inner = shared.clone();
+        let handle = thread::Builder::new()
+            .name("spsc-rs-shm".into())
+            .spawn(move || {
+                let futex = unsafe { &*futex.get() };
+                while !inner.stopped.load(Ordering::Acquire) {
+                    if inner.armed.swap(false, Ordering::AcqRel) {
+                        futex.wait(None, || ready() || inner.stopped.load(Ordering::Acquire));
+                        inner.waker.wake_by_ref();
+                    } else {
+                        thread::park();
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            shared,
+            thread: handle.thread().clone(),
+        })
+    }
+
+    /// Registers the task, and asks the helper thread to wake it once the side is ready.
+    /// The caller must check the condition again afterwards.
+    pub(crate) fn arm(&self, waker: &std::task::Waker) {
+        self.shared.waker.register(waker);
+        self.shared.armed.store(true, Ordering::Release);
+        self.thread.unpark();
+    }
+
+    /// Stops the helper thread, the futex must still be alive.
+    pub(crate) fn stop(&self, futex: &Futex) {
+        self.shared.stopped.store(true, Ordering::Release);
+        futex.interrupt();
+        self.thread.unpark();
+    }
+}
+
+/// The futex lives in a shared mapping, which the helper thread keeps alive.
+struct FutexPtr(*const Futex);
+
+unsafe impl Send for FutexPtr {}
+
+impl FutexPtr {
+    fn get(&self) -> *const Futex {
+        self.0
+    }
+}
@@ -0,0 +1,313 @@
+use super::futex::Futex;
+use crate::bounded::ring::{And, Indexer, Remainder};
+use std::ffi::CString;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::{io, mem, ptr};
+
+const MAGIC: u64 = u64::from_ne_bytes(*b"spsc-shm");
+
+/// Bumped whenever the layout of the header changes.
+pub(crate) const VERSION: u32 = 1;
+
+/// The positions are moved by [`And`], the capacity is a power of two.
+const INDEXER_AND: u32 = 0;
+/// The positions are moved by [`Remainder`].
+const INDEXER_REMAINDER: u32 = 1;
+
+/// The header at the start of the shared mapping, the slots follow it.
+///
+/// The positions have the same meaning as in the in-process ring, and are moved by the
+/// same indexers, so every slot can be used. They are `usize` values of the indexer, so
+/// both processes must have the same pointer width.
+#[repr(C)]
+pub(crate) struct Header {
+    /// Written last by the creator, so a half initialized header is never used.
+    magic: AtomicU64,
+    version: u32,
+    indexer: u32,
+    pos_bits: u32,
+    capacity: u64,
+    elem_size: u64,
+    elem_align: u64,
+    pub(crate) closed: AtomicU32,
+    sender_attached: AtomicU32,
+    receiver_attached: AtomicU32,
+    pub(crate) producer: Cursor,
+    pub(crate) consumer: Cursor,
+}
+
+/// A position in its own cache line, with the futex the other side waits on.
+#[repr(C, align(64))]
+pub(crate) struct Cursor {
+    pub(crate) pos: AtomicU64,
+    pub(crate) futex: Futex,
+}
+
+/// A shared mapping which holds a header and `capacity` slots of `elem_size` bytes.
+pub(crate) struct Mapping {
+    ptr: *mut u8,
+    len: usize,
+    indexer: ShmIndexer,
+    slots_offset: usize,
+    elem_size: usize,
+}
+
+unsafe impl Send for Mapping {}
+unsafe impl Sync for Mapping {}
+
+fn slots_offset(elem_align: usize) -> usize {
+    let align = elem_align.max(mem::align_of::<Header>());
+    (mem::size_of::<Header>() + align - 1) & !(align - 1)
+}
+
+impl Mapping {
+    /// Initializes a new channel in the empty shared memory behind `fd`.
+    pub(crate) fn create<T>(fd: &OwnedFd, size: usize) -> io::Result<Self> {
+        let capacity = size.max(1);
+        let len = capacity
+            .checked_mul(mem::size_of::<T>())
+            .and_then(|slots| slots.checked_add(slots_offset(mem::align_of::<T>())))
+            .expect("capacity overflow");
+
+        if unsafe { libc::ftruncate(fd.as_raw_fd(), len as libc::off_t) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        let mapping = Self::map(fd, len)?;
+        let p2 = capacity.is_power_of_two();
+        unsafe {
+            // The file is new, so nobody else looks at the header before the magic is set.
+            let header = mapping.ptr as *mut Header;
+            ptr::write(
+                header,
+                Header {
+                    magic: AtomicU64::new(0),
+                    version: VERSION,
+                    indexer: if p2 { INDEXER_AND } else { INDEXER_REMAINDER },
+                    pos_bits: usize::BITS,
+                    capacity: capacity as u64,
+                    elem_size: mem::size_of::<T>() as u64,
+                    elem_align: mem::align_of::<T>() as u64,
+                    closed: AtomicU32::new(0),
+                    sender_attached: AtomicU32::new(0),
+                    receiver_attached: AtomicU32::new(0),
+                    producer: Cursor::default(),
+                    consumer: Cursor::default(),
+                },
+            );
+            (*header).magic.store(MAGIC, Ordering::Release);
+        }
+
+        mapping.validate::<T>()
+    }
+
+    /// Maps an existing channel, and checks it was created for `T`.
+    pub(crate) fn open<T>(fd: &OwnedFd) -> io::Result<Self> {
+        let mut stat = mem::MaybeUninit::<libc::stat>::uninit();
+        if unsafe { libc::fstat(fd.as_raw_fd(), stat.as_mut_ptr()) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let len = unsafe { stat.assume_init() }.st_size as usize;
+        if len < mem::size_of::<Header>() {
+            return Err(invalid("the shared memory is not a channel"));
+        }
+
+        Self::map(fd, len)?.validate::<T>()
+    }
+
+    fn map(fd: &OwnedFd, len: usize) -> io::Result<Self> {
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        Ok(Self {
+            ptr: ptr.cast(),
+            len,
+            indexer: ShmIndexer::And(And::new(1)),
+            slots_offset: 0,
+            elem_size: 0,
+        })
+    }
+
+    fn validate<T>(mut self) -> io::Result<Self> {
+        let header = self.header();
+        if header.magic.load(Ordering::Acquire) != MAGIC {
+            return Err(invalid("the shared memory is not a channel"));
+        }
+        if header.version != VERSION {
+            return Err(invalid("the channel is created by an incompatible version"));
+        }
+        if header.elem_size != mem::size_of::<T>() as u64
+            || header.elem_align != mem::align_of::<T>() as u64
+        {
+            return Err(invalid("the channel is created for another item type"));
+        }
+
+        if header.pos_bits != usize::BITS {
+            return Err(invalid("the channel is created with another pointer width"));
+        }
+
+        // The remainder indexer keeps its positions below twice the capacity.
+        let capacity = usize::try_from(header.capacity)
+            .ok()
+            .filter(|capacity| (1..=usize::MAX / 2).contains(capacity))
+            .ok_or_else(|| invalid("the channel has an invalid capacity"))?;
+        let indexer = match header.indexer {
+            INDEXER_AND if capacity.is_power_of_two() => ShmIndexer::And(And::new(capacity)),
+            INDEXER_REMAINDER => ShmIndexer::Remainder(Remainder::new(capacity)),
+            _ => return Err(invalid("the channel has an invalid capacity")),
+        };
+
+        let slots_offset = slots_offset(mem::align_of::<T>());
+        let len = capacity
+            .checked_mul(mem::size_of::<T>())
+            .and_then(|slots| slots.checked_add(slots_offset));
+        if len.is_none_or(|len| len > self.len) {
+            return Err(invalid("the shared memory is too small"));
+        }
+
+        self.indexer = indexer;
+        self.slots_offset = slots_offset;
+        self.elem_size = mem::size_of::<T>();
+        Ok(self)
+    }
+
+    pub(crate) fn header(&self) -> &Header {
+        unsafe { &*(self.ptr as *const Header) }
+    }
+
+    /// Marks one side as taken, so each side can only be used once.
+    pub(crate) fn attach(&self, sender: bool) -> io::Result<()> {
+        let header = self.header();
+        let flag = if sender {
+            &header.sender_attached
+        } else {
+            &header.receiver_attached
+        };
+
+        if flag.swap(1, Ordering::AcqRel) == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::new(
+                io::ErrorKind::AddrInUse,
+                "the channel side is already taken",
+            ))
+        }
+    }
+
+    pub(crate) fn indexer(&self) -> &impl Indexer {
+        &self.indexer
+    }
+
+    /// The slot at the position `pos`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the position was corrupted by the other process, and is outside of the ring.
+    pub(crate) fn slot(&self, pos: usize) -> *mut u8 {
+        let idx = self.indexer.index(pos);
+        assert!(idx < self.indexer.cap(), "the channel is corrupted");
+        unsafe { self.ptr.add(self.slots_offset + idx * self.elem_size) }
+    }
+}
+
+impl Drop for Mapping {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr.cast(), self.len);
+        }
+    }
+}
+
+/// The indexer of the channel, only known when the mapping is validated.
+enum ShmIndexer {
+    And(And),
+    Remainder(Remainder),
+}
+
+impl Indexer for ShmIndexer {
+    fn index(&self, pos: usize) -> usize {
+        match self {
+            ShmIndexer::And(indexer) => indexer.index(pos),
+            ShmIndexer::Remainder(indexer) => indexer.index(pos),
+        }
+    }
+
+    fn cap(&self) -> usize {
+        match self {
+            ShmIndexer::And(indexer) => indexer.cap(),
+            ShmIndexer::Remainder(indexer) => indexer.cap(),
+        }
+    }
+
+    fn advance(&self, pos: usize, n: usize) -> usize {
+        match self {
+            ShmIndexer::And(indexer) => indexer.advance(pos, n),
+            ShmIndexer::Remainder(indexer) => indexer.advance(pos, n),
+        }
+    }
+
+    fn distance(&self, from: usize, to: usize) -> usize {
+        match self {
+            ShmIndexer::And(indexer) => indexer.distance(from, to),
+            ShmIndexer::Remainder(indexer) => indexer.distance(from, to),
+        }
+    }
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self {
+            pos: AtomicU64::new(0),
+            futex: Futex::default(),
+        }
+    }
+}
+
+fn invalid(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+pub(crate) fn shm_open(name: &str, flags: libc::c_int) -> io::Result<OwnedFd> {
+    let name = c_name(name)?;
+    let fd =
+        unsafe { libc::shm_open(name.as_ptr(), flags | libc::O_RDWR | libc::O_CLOEXEC, 0o600) };
+    if fd < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+}
+
+pub(crate) fn shm_unlink(name: &str) -> io::Result<()> {
+    let name = c_name(name)?;
+    if unsafe { libc::shm_unlink(name.as_ptr()) } < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn memfd() -> io::Result<OwnedFd> {
+    let fd = unsafe { libc::memfd_create(c"spsc-rs-shm".as_ptr(), libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        Err(io::Error::last_os_error())
+    } else {
+        Ok(unsafe { OwnedFd::from_raw_fd(fd) })
+    }
+}
+
+fn c_name(name: &str) -> io::Result<CString> {
+    CString::new(name).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "nul in the name"))
+}
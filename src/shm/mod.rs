@@ -0,0 +1,397 @@
+//! A bounded channel between two processes, through shared memory.
+//!
+//! The channel lives in a shared mapping: a header with the positions, the capacity and a layout
+//! version, followed by the slots. One process creates it with [`Shm::create`] (by name) or
+//! [`Shm::anonymous`] (by fd), and the other one maps the same memory with [`Shm::open`] or
+//! [`Shm::from_fd`]. Then each process takes one side with [`Shm::sender`] or [`Shm::receiver`].
+//!
+//! The items are copied into the mapping as they are, so they must be [`Pod`]. The wakers can't
+//! cross processes, so the waiting side sleeps on a futex in the shared header instead. The
+//! first time an async method has to wait, its side spawns a helper thread, which waits on the
+//! futex and wakes the task.
+//!
+//! Only available on Linux with the `shm` feature.
+
+mod futex;
+mod layout;
+
+use self::futex::{AsyncWaiter, Futex};
+use self::layout::Mapping;
+use crate::bounded::ring::Indexer;
+use crate::error::{SendError, TryRecvError, TrySendError};
+use futures_util::future::poll_fn;
+use std::marker::PhantomData;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd, RawFd};
+use std::sync::atomic::Ordering;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+use std::{io, ptr};
+
+/// Types which can be copied to another process as plain bytes.
+///
+/// # Safety
+///
+/// The type must be `Copy`, have no pointers or references, and every bit pattern must be
+/// a valid value. `#[repr(C)]` structs of `Pod` fields without padding are fine.
+pub unsafe trait Pod: Copy + 'static {}
+
+macro_rules! impl_pod {
+    ($($t:ty),*) => {
+        $(unsafe impl Pod for $t {})*
+    };
+}
+
+impl_pod!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+unsafe impl<T: Pod, const N: usize> Pod for [T; N] {}
+
+/// A shared memory channel, which gives out its sender and receiver.
+pub struct Shm<T> {
+    fd: OwnedFd,
+    mapping: Arc<Mapping>,
+    _marker: PhantomData<T>,
+}
+
+impl<T: Pod> Shm<T> {
+    /// Creates a channel in a new POSIX shared memory object, which can buffer `size` items.
+    ///
+    /// The `name` is like `/my-channel`, and it fails if the object already exists.
+    pub fn create(name: &str, size: usize) -> io::Result<Self> {
+        let fd = layout::shm_open(name, libc::O_CREAT | libc::O_EXCL)?;
+        Self::with_fd(fd, |fd| Mapping::create::<T>(fd, size))
+    }
+
+    /// Opens a channel created by [`create`] in another process.
+    ///
+    /// [`create`]: Shm::create
+    pub fn open(name: &str) -> io::Result<Self> {
+        let fd = layout::shm_open(name, 0)?;
+        Self::with_fd(fd, Mapping::open::<T>)
+    }
+
+    /// Removes the name of a shared memory object, the processes which have mapped it
+    /// can still use it.
+    pub fn unlink(name: &str) -> io::Result<()> {
+        layout::shm_unlink(name)
+    }
+
+    /// Creates a channel in an anonymous memory file, which can buffer `size` items.
+    ///
+    /// The file is only reachable through its fd, which can be sent to the other process
+    /// over a Unix socket. The fd is close-on-exec.
+    pub fn anonymous(size: usize) -> io::Result<Self> {
+        Self::with_fd(layout::memfd()?, |fd| Mapping::create::<T>(fd, size))
+    }
+
+    /// Maps a channel from the fd of [`anonymous`] or [`create`].
+    ///
+    /// [`anonymous`]: Shm::anonymous
+    /// [`create`]: Shm::create
+    pub fn from_fd(fd: OwnedFd) -> io::Result<Self> {
+        Self::with_fd(fd, Mapping::open::<T>)
+    }
+
+    /// Takes the sending side, which can only be taken once among all the processes.
+    pub fn sender(&self) -> io::Result<ShmSender<T>> {
+        self.mapping.attach(true)?;
+        Ok(ShmSender {
+            mapping: self.mapping.clone(),
+            waiter: None,
+            _marker: PhantomData,
+        })
+    }
+
+    /// Takes the receiving side, which can only be taken once among all the processes.
+    pub fn receiver(&self) -> io::Result<ShmReceiver<T>> {
+        self.mapping.attach(false)?;
+        Ok(ShmReceiver {
+            mapping: self.mapping.clone(),
+            waiter: None,
+            _marker: PhantomData,
+        })
+    }
+
+    /// The number of items the channel can buffer.
+    pub fn capacity(&self) -> usize {
+        self.mapping.indexer().cap()
+    }
+
+    fn with_fd(fd: OwnedFd, map: impl FnOnce(&OwnedFd) -> io::Result<Mapping>) -> io::Result<Self> {
+        let mapping = map(&fd)?;
+        Ok(Self {
+            fd,
+            mapping: Arc::new(mapping),
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T> AsFd for Shm<T> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl<T> AsRawFd for Shm<T> {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+/// The sending side of a [`Shm`] channel.
+pub struct ShmSender<T> {
+    mapping: Arc<Mapping>,
+    waiter: Option<AsyncWaiter>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Drop for ShmSender<T> {
+    fn drop(&mut self) {
+        let header = self.mapping.header();
+        header.closed.store(1, Ordering::Release);
+        header.producer.futex.notify();
+        if let Some(waiter) = &self.waiter {
+            waiter.stop(&header.consumer.futex);
+        }
+    }
+}
+
+impl<T: Pod> ShmSender<T> {
+    /// Sends an item without waiting, the item is returned back if the channel is full or closed.
+    pub fn try_send(&mut self, item: T) -> Result<(), TrySendError<T>> {
+        let header = self.mapping.header();
+        if header.closed.load(Ordering::Acquire) != 0 {
+            return Err(TrySendError {
+                err: SendError::Disconnected,
+                val: item,
+            });
+        }
+
+        let now = header.producer.pos.load(Ordering::Relaxed) as usize;
+        if !has_room(&self.mapping) {
+            return Err(TrySendError {
+                err: SendError::Full,
+                val: item,
+            });
+        }
+
+        unsafe {
+            let slot = self.mapping.slot(now) as *mut T;
+            ptr::write(slot, item);
+        }
+        let next = self.mapping.indexer().advance(now, 1);
+        header.producer.pos.store(next as u64, Ordering::Release);
+        header.producer.futex.notify();
+        Ok(())
+    }
+
+    /// Sends an item, blocking the current thread until there is capacity.
+    pub fn blocking_send(&mut self, item: T) -> Result<(), TrySendError<T>> {
+        self.send_deadline(item, None)
+    }
+
+    /// Sends an item, blocking the current thread for at most `timeout` to wait for capacity.
+    pub fn send_timeout(&mut self, item: T, timeout: Duration) -> Result<(), TrySendError<T>> {
+        self.send_deadline(item, Instant::now().checked_add(timeout))
+    }
+
+    /// Sends an item, waiting for capacity.
+    ///
+    /// The futex can't be polled, so the first time the channel is full, a helper thread is
+    /// spawned to wait on it. If it can't be spawned, the task keeps polling instead.
+    pub async fn send(&mut self, item: T) -> Result<(), TrySendError<T>> {
+        let mut item = Some(item);
+        poll_fn(|cx| self.poll_send(cx, &mut item)).await
+    }
+
+    /// Returns whether this channel is closed.
+    pub fn is_closed(&self) -> bool {
+        self.mapping.header().closed.load(Ordering::Acquire) != 0
+    }
+
+    fn send_deadline(&mut self, item: T, deadline: Option<Instant>) -> Result<(), TrySendError<T>> {
+        let mapping = &self.mapping;
+        mapping
+            .header()
+            .consumer
+            .futex
+            .wait(deadline, || has_room(mapping) || is_closed(mapping));
+        self.try_send(item)
+    }
+
+    fn poll_send(
+        &mut self,
+        cx: &mut Context<'_>,
+        item: &mut Option<T>,
+    ) -> Poll<Result<(), TrySendError<T>>> {
+        let val = item.take().expect("polled after completion");
+        match self.try_send(val) {
+            Err(err) if err.is_full() => {
+                self.arm(cx);
+
+                // The receiver may have made room before the waiter is armed.
+                match self.try_send(err.into_inner()) {
+                    Err(err) if err.is_full() => {
+                        *item = Some(err.into_inner());
+                        Poll::Pending
+                    }
+                    res => Poll::Ready(res),
+                }
+            }
+            res => Poll::Ready(res),
+        }
+    }
+
+    /// Arms the helper thread, which is spawned on the first call.
+    ///
+    /// If it can't be spawned, the task is woken right away to poll again, like with
+    /// [`BusySpin`], and the spawn is retried on the next call.
+    ///
+    /// [`BusySpin`]: crate::wait::BusySpin
+    fn arm(&mut self, cx: &mut Context<'_>) {
+        if self.waiter.is_none() {
+            let mapping = self.mapping.clone();
+            let futex: *const Futex = &self.mapping.header().consumer.futex;
+            self.waiter =
+                AsyncWaiter::spawn(futex, move || has_room(&mapping) || is_closed(&mapping)).ok();
+        }
+
+        match &self.waiter {
+            Some(waiter) => waiter.arm(cx.waker()),
+            None => cx.waker().wake_by_ref(),
+        }
+    }
+}
+
+/// The receiving side of a [`Shm`] channel.
+pub struct ShmReceiver<T> {
+    mapping: Arc<Mapping>,
+    waiter: Option<AsyncWaiter>,
+    _marker: PhantomData<T>,
+}
+
+impl<T> Drop for ShmReceiver<T> {
+    fn drop(&mut self) {
+        let header = self.mapping.header();
+        header.closed.store(1, Ordering::Release);
+        header.consumer.futex.notify();
+        if let Some(waiter) = &self.waiter {
+            waiter.stop(&header.producer.futex);
+        }
+    }
+}
+
+impl<T: Pod> ShmReceiver<T> {
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        match self.pop() {
+            Some(item) => Ok(item),
+            // The same as the in-process channel, pop again after seeing the channel closed.
+            None if self.is_closed() => self.pop().ok_or(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+
+    /// Receives an item, blocking the current thread until one is available.
+    ///
+    /// `None` is returned once the channel is closed and all the remaining items are consumed.
+    pub fn blocking_recv(&mut self) -> Option<T> {
+        self.recv_deadline(None).ok()
+    }
+
+    /// Receives an item, blocking the current thread for at most `timeout`.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, TryRecvError> {
+        self.recv_deadline(Instant::now().checked_add(timeout))
+    }
+
+    /// Receives an item, waiting until one is available.
+    ///
+    /// The futex can't be polled, so the first time the channel is empty, a helper thread is
+    /// spawned to wait on it. If it can't be spawned, the task keeps polling instead.
+    pub async fn recv(&mut self) -> Option<T> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        match self.try_recv() {
+            Ok(item) => return Poll::Ready(Some(item)),
+            Err(TryRecvError::Disconnected) => return Poll::Ready(None),
+            Err(TryRecvError::Empty) => {}
+        }
+
+        self.arm(cx);
+
+        // The sender may have sent an item before the waiter is armed.
+        match self.try_recv() {
+            Ok(item) => Poll::Ready(Some(item)),
+            Err(TryRecvError::Disconnected) => Poll::Ready(None),
+            Err(TryRecvError::Empty) => Poll::Pending,
+        }
+    }
+
+    pub fn is_closed(&self) -> bool {
+        is_closed(&self.mapping)
+    }
+
+    pub fn close(&mut self) {
+        let header = self.mapping.header();
+        header.closed.store(1, Ordering::Release);
+        header.consumer.futex.notify();
+    }
+
+    fn recv_deadline(&mut self, deadline: Option<Instant>) -> Result<T, TryRecvError> {
+        let mapping = &self.mapping;
+        mapping
+            .header()
+            .producer
+            .futex
+            .wait(deadline, || has_item(mapping) || is_closed(mapping));
+        self.try_recv()
+    }
+
+    fn pop(&mut self) -> Option<T> {
+        if !has_item(&self.mapping) {
+            return None;
+        }
+
+        let header = self.mapping.header();
+        let now = header.consumer.pos.load(Ordering::Relaxed) as usize;
+        let item = unsafe { ptr::read(self.mapping.slot(now) as *const T) };
+        let next = self.mapping.indexer().advance(now, 1);
+        header.consumer.pos.store(next as u64, Ordering::Release);
+        header.consumer.futex.notify();
+        Some(item)
+    }
+
+    /// The same as `ShmSender::arm`, for the items.
+    fn arm(&mut self, cx: &mut Context<'_>) {
+        if self.waiter.is_none() {
+            let mapping = self.mapping.clone();
+            let futex: *const Futex = &self.mapping.header().producer.futex;
+            self.waiter =
+                AsyncWaiter::spawn(futex, move || has_item(&mapping) || is_closed(&mapping)).ok();
+        }
+
+        match &self.waiter {
+            Some(waiter) => waiter.arm(cx.waker()),
+            None => cx.waker().wake_by_ref(),
+        }
+    }
+}
+
+fn has_room(mapping: &Mapping) -> bool {
+    let header = mapping.header();
+    let p = header.producer.pos.load(Ordering::Acquire) as usize;
+    let c = header.consumer.pos.load(Ordering::Acquire) as usize;
+    let indexer = mapping.indexer();
+    indexer.distance(c, p) < indexer.cap()
+}
+
+fn has_item(mapping: &Mapping) -> bool {
+    let header = mapping.header();
+    header.producer.pos.load(Ordering::Acquire) != header.consumer.pos.load(Ordering::Acquire)
+}
+
+fn is_closed(mapping: &Mapping) -> bool {
+    mapping.header().closed.load(Ordering::Acquire) != 0
+}
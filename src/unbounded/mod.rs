@@ -1,48 +1,106 @@
-use crate::atomic_waker::AtomicWaker;
+use crate::allocator::{AllocError, Allocator, Global};
+#[cfg(feature = "std")]
+use crate::blocking;
 use crate::error::{SendError, TryRecvError, TrySendError};
-use crate::loom::{Arc, AtomicBool, Ordering};
+use crate::loom::{AtomicUsize, Counter, Ordering};
+#[cfg(feature = "std")]
+use crate::notify;
 use crate::unbounded::queue::Queue;
+use crate::wait::{AsyncWaker, WaitStrategy};
+use alloc::vec::Vec;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use core::time::Duration;
 use futures_util::future::poll_fn;
 use futures_util::Stream;
-use std::pin::Pin;
-use std::task::{Context, Poll};
 
-mod queue;
+pub(crate) mod queue;
 pub mod wrapper;
 
 pub fn unbounded_channel<T>() -> (UnboundedSender<T>, UnboundedReceiver<T>) {
+    from_queue(Queue::new())
+}
+
+/// Creates an unbounded channel whose blocks are allocated by `alloc`, see [`allocator`].
+///
+/// Only the first block is allocated here, if a later one can't be allocated,
+/// [`handle_alloc_error`] is called like for the global allocator. The handles keep `A`
+/// as a type parameter, so every block is allocated without dynamic dispatch.
+///
+/// [`allocator`]: crate::allocator
+/// [`handle_alloc_error`]: alloc::alloc::handle_alloc_error
+pub fn unbounded_channel_with_alloc<T, A: Allocator>(
+    alloc: A,
+) -> Result<Halves<T, AsyncWaker, AtomicUsize, A>, AllocError> {
+    Queue::new_in(alloc).map(from_queue)
+}
+
+/// The two halves of a channel, as returned by `from_queue`.
+type Halves<T, W, C, A> = (UnboundedSender<T, W, C, A>, UnboundedReceiver<T, W, C, A>);
+
+pub(crate) fn from_queue<T, W, C, A>(queue: Queue<T, C, A>) -> Halves<T, W, C, A>
+where
+    W: WaitStrategy + Default,
+    C: Counter,
+    A: Allocator,
+{
     let shared = Shared {
-        queue: Queue::new(),
-        consumer: AtomicWaker::default(),
-        closed: AtomicBool::new(false),
+        queue,
+        consumer: W::default(),
+        producer: W::default(),
+        closed: C::default(),
     };
-    let ptr = Arc::new(shared);
+    let ptr = C::share(shared);
     (
         UnboundedSender { inner: ptr.clone() },
-        UnboundedReceiver { inner: ptr },
+        UnboundedReceiver {
+            inner: ptr,
+            notify: None,
+        },
     )
 }
 
-struct Shared<T> {
-    queue: Queue<T>,
-    consumer: AtomicWaker,
-    closed: AtomicBool,
+/// The same as the bounded `Shared`, `C` is a `Cell` in the [`local`] channels.
+///
+/// [`local`]: crate::local
+struct Shared<T, W, C: Counter, A: Allocator> {
+    queue: Queue<T, C, A>,
+    consumer: W,
+    /// The sender never waits for room, only the `set_notify` callback is registered here.
+    producer: W,
+    /// Non-zero once either half closed the channel.
+    closed: C,
+}
+
+impl<T, W, C: Counter, A: Allocator> Shared<T, W, C, A> {
+    fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire) != 0
+    }
+
+    fn set_closed(&self) {
+        self.closed.store(1, Ordering::Release);
+    }
 }
 
-pub struct UnboundedSender<T> {
-    inner: Arc<Shared<T>>,
+pub struct UnboundedSender<
+    T,
+    W: WaitStrategy = AsyncWaker,
+    C: Counter = AtomicUsize,
+    A: Allocator = Global,
+> {
+    inner: C::Ptr<Shared<T, W, C, A>>,
 }
 
-impl<T> Drop for UnboundedSender<T> {
+impl<T, W: WaitStrategy, C: Counter, A: Allocator> Drop for UnboundedSender<T, W, C, A> {
     fn drop(&mut self) {
         // we need to wake up the receiver before
         // the sender was totally dropped, otherwise the receiver may hang up.
-        self.inner.closed.store(true, Ordering::Release);
+        self.inner.set_closed();
         self.inner.consumer.wake_by_ref();
     }
 }
 
-impl<T> UnboundedSender<T> {
+impl<T, W: WaitStrategy, C: Counter, A: Allocator> UnboundedSender<T, W, C, A> {
     pub fn send(&mut self, t: T) -> Result<(), TrySendError<T>> {
         if self.is_closed() {
             Err(TrySendError {
@@ -56,8 +114,61 @@ impl<T> UnboundedSender<T> {
         }
     }
 
+    /// Copies all the items to the channel, publishing them to the receiver at once.
+    pub fn extend_from_slice(&mut self, items: &[T]) -> Result<(), SendError>
+    where
+        T: Copy,
+    {
+        if self.is_closed() {
+            return Err(SendError::Disconnected);
+        }
+
+        if !items.is_empty() {
+            // Safety: the same as `push`, there is only one sender.
+            unsafe { self.inner.queue.extend_from_slice(items) }
+            self.inner.consumer.wake_by_ref();
+        }
+        Ok(())
+    }
+
+    /// Sends a value from synchronous code.
+    ///
+    /// The unbounded channel never waits for capacity, so this is the same as [`send`]. It
+    /// exists to keep the blocking API the same as the bounded [`Sender`].
+    ///
+    /// [`send`]: UnboundedSender::send
+    /// [`Sender`]: crate::bounded::Sender
+    pub fn blocking_send(&mut self, t: T) -> Result<(), TrySendError<T>> {
+        self.send(t)
+    }
+
+    /// Sends a value from synchronous code.
+    ///
+    /// The unbounded channel never waits for capacity, so `timeout` is never reached and this
+    /// is the same as [`send`].
+    ///
+    /// [`send`]: UnboundedSender::send
+    pub fn send_timeout(&mut self, t: T, _timeout: Duration) -> Result<(), TrySendError<T>> {
+        self.send(t)
+    }
+
     pub fn is_closed(&self) -> bool {
-        self.inner.closed.load(Ordering::Acquire)
+        self.inner.is_closed()
+    }
+
+    /// Calls `notify` once the receiver closes the channel, for schedulers which are neither
+    /// async runtimes nor threads.
+    ///
+    /// The unbounded channel never runs out of room, so this is the only event the sender
+    /// can wait for. The callback fires right away if the channel is already closed.
+    #[cfg(feature = "std")]
+    pub fn set_notify(&mut self, notify: std::sync::Arc<dyn Fn() + Send + Sync>) {
+        let notify = notify::waker(notify);
+        self.inner.producer.register(&notify);
+        // The same as `poll_recv`, check again after registering.
+        if self.is_closed() {
+            notify.wake_by_ref();
+        }
     }
 
     fn push(&mut self, t: T) {
@@ -68,17 +179,24 @@ impl<T> UnboundedSender<T> {
     }
 }
 
-pub struct UnboundedReceiver<T> {
-    inner: Arc<Shared<T>>,
+pub struct UnboundedReceiver<
+    T,
+    W: WaitStrategy = AsyncWaker,
+    C: Counter = AtomicUsize,
+    A: Allocator = Global,
+> {
+    inner: C::Ptr<Shared<T, W, C, A>>,
+    /// The callback given to `set_notify`.
+    notify: Option<Waker>,
 }
 
-impl<T> Drop for UnboundedReceiver<T> {
+impl<T, W: WaitStrategy, C: Counter, A: Allocator> Drop for UnboundedReceiver<T, W, C, A> {
     fn drop(&mut self) {
         self.close()
     }
 }
 
-impl<T> Stream for UnboundedReceiver<T> {
+impl<T, W: WaitStrategy, C: Counter, A: Allocator> Stream for UnboundedReceiver<T, W, C, A> {
     type Item = T;
 
     fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
@@ -86,7 +204,32 @@ impl<T> Stream for UnboundedReceiver<T> {
     }
 }
 
-impl<T> UnboundedReceiver<T> {
+// The same as the bounded channels, not for the `local` ones.
+#[cfg(feature = "std")]
+impl<T, W: WaitStrategy, A: Allocator> UnboundedReceiver<T, W, AtomicUsize, A> {
+    /// Receives the next value, parking the current thread until one is available.
+    ///
+    /// This is the synchronous version of [`receive`]. `None` is returned once the channel
+    /// is closed and all the remaining values are consumed.
+    ///
+    /// [`receive`]: UnboundedReceiver::receive
+    pub fn blocking_recv(&mut self) -> Option<T> {
+        blocking::block_on(|cx| self.poll_recv(cx))
+    }
+
+    /// Receives the next value, parking the current thread for at most `timeout`.
+    ///
+    /// Returns `Err(TryRecvError::Empty)` if no value arrives before the timeout elapses.
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<T, TryRecvError> {
+        match blocking::block_on_timeout(timeout, |cx| self.poll_recv(cx)) {
+            Some(Some(item)) => Ok(item),
+            Some(None) => Err(TryRecvError::Disconnected),
+            None => Err(TryRecvError::Empty),
+        }
+    }
+}
+
+impl<T, W: WaitStrategy, C: Counter, A: Allocator> UnboundedReceiver<T, W, C, A> {
     pub async fn receive(&mut self) -> Option<T> {
         poll_fn(|cx| self.poll_recv(cx)).await
     }
@@ -97,7 +240,9 @@ impl<T> UnboundedReceiver<T> {
         }
 
         self.inner.consumer.register(cx.waker());
-        if self.inner.queue.is_empty() {
+
+        // The sender could be closed before the register operation, see `poll_recv`.
+        if self.inner.queue.is_empty() && !self.is_closed() {
             Poll::Pending
         } else {
             Poll::Ready(())
@@ -123,6 +268,7 @@ impl<T> UnboundedReceiver<T> {
                         Some(item) => Ok(item),
                     }
                 } else {
+                    self.arm_notify();
                     Err(TryRecvError::Empty)
                 }
             }
@@ -153,12 +299,110 @@ impl<T> UnboundedReceiver<T> {
         }
     }
 
+    /// Moves up to `limit` items to the end of `buf` without waiting, returns the number
+    /// of received items.
+    pub fn try_recv_many(&mut self, buf: &mut Vec<T>, limit: usize) -> usize {
+        // Safety: the same as `try_pop`, there is only one receiver.
+        unsafe { self.inner.queue.pop_into(buf, limit) }
+    }
+
+    /// Receives up to `limit` items into `buf`, waiting until there is at least one.
+    ///
+    /// Returns `0` if `limit` is `0`, or once the channel is closed and all the remaining
+    /// values are consumed.
+    pub async fn recv_many(&mut self, buf: &mut Vec<T>, limit: usize) -> usize {
+        poll_fn(|cx| self.poll_recv_many(cx, buf, limit)).await
+    }
+
+    pub fn poll_recv_many(
+        &mut self,
+        cx: &mut Context<'_>,
+        buf: &mut Vec<T>,
+        limit: usize,
+    ) -> Poll<usize> {
+        if limit == 0 {
+            return Poll::Ready(0);
+        }
+
+        let n = self.try_recv_many(buf, limit);
+        if n > 0 {
+            return Poll::Ready(n);
+        }
+
+        self.inner.consumer.register(cx.waker());
+
+        // The same as `poll_recv`, poll again after registering, and the closed
+        // check must come before the last poll.
+        if self.is_closed() {
+            Poll::Ready(self.try_recv_many(buf, limit))
+        } else {
+            match self.try_recv_many(buf, limit) {
+                0 => Poll::Pending,
+                n => Poll::Ready(n),
+            }
+        }
+    }
+
+    /// Returns the head item without popping it.
+    pub fn peek(&self) -> Option<&T> {
+        // Safety: only the receiver can pop, and it can't while the item is borrowed.
+        unsafe { self.inner.queue.peek().map(|ptr| &*ptr) }
+    }
+
+    /// The mutable version of [`peek`].
+    ///
+    /// [`peek`]: UnboundedReceiver::peek
+    pub fn peek_mut(&mut self) -> Option<&mut T> {
+        unsafe { self.inner.queue.peek().map(|ptr| &mut *ptr) }
+    }
+
+    /// Pops the head item only if `pred` returns `true` for it.
+    pub fn recv_if(&mut self, pred: impl FnOnce(&T) -> bool) -> Option<T> {
+        match self.peek() {
+            Some(item) if pred(item) => self.try_pop(),
+            _ => None,
+        }
+    }
+
+    /// Returns an iterator over the items in the channel, without popping them.
+    ///
+    /// Items sent after this method is called are not visited.
+    pub fn iter_pending(&self) -> impl Iterator<Item = &T> + '_ {
+        unsafe { self.inner.queue.iter() }
+    }
+
     pub fn is_closed(&self) -> bool {
-        self.inner.closed.load(Ordering::Acquire)
+        self.inner.is_closed()
     }
 
     pub fn close(&mut self) {
-        self.inner.closed.store(true, Ordering::Release)
+        self.inner.set_closed();
+        // only the `set_notify` callback of the sender can be registered.
+        self.inner.producer.wake_by_ref();
+    }
+
+    /// Calls `notify` when there are items to receive, or the channel is closed, for
+    /// schedulers which are neither async runtimes nor threads.
+    ///
+    /// The same as the bounded `Receiver::set_notify`: the callback fires once per
+    /// registration, and is registered right away and again each time [`try_recv`] finds
+    /// the channel empty.
+    ///
+    /// [`try_recv`]: UnboundedReceiver::try_recv
+    #[cfg(feature = "std")]
+    pub fn set_notify(&mut self, notify: std::sync::Arc<dyn Fn() + Send + Sync>) {
+        self.notify = Some(notify::waker(notify));
+        self.arm_notify();
+    }
+
+    /// Registers the `set_notify` callback, then checks again like `poll_recv` does.
+    fn arm_notify(&self) {
+        if let Some(notify) = &self.notify {
+            self.inner.consumer.register(notify);
+            if !self.inner.queue.is_empty() || self.is_closed() {
+                notify.wake_by_ref();
+            }
+        }
     }
 
     fn try_pop(&mut self) -> Option<T> {
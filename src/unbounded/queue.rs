@@ -1,6 +1,12 @@
-use crate::loom::{AtomicPtr, AtomicUsize, Ordering, UnsafeCell};
-use std::mem::MaybeUninit;
-use std::ptr::{null_mut, NonNull};
+use crate::allocator::{AllocError, Allocator, Global};
+use crate::loom::{AtomicPtr, AtomicUsize, Counter, Ordering, UnsafeCell};
+use alloc::alloc::handle_alloc_error;
+use alloc::vec::Vec;
+use core::alloc::Layout;
+use core::cmp;
+use core::marker::PhantomData;
+use core::mem::MaybeUninit;
+use core::ptr::{self, null_mut, NonNull};
 
 #[cfg(not(loom))]
 const BLOCK_SIZE: usize = 128;
@@ -34,6 +40,23 @@ impl<T> Block<T> {
         }
     }
 
+    const LAYOUT: Layout = Layout::new::<Self>();
+
+    fn alloc(alloc: &impl Allocator) -> Result<NonNull<Self>, AllocError> {
+        let block = alloc.allocate(Self::LAYOUT)?.cast::<Self>();
+        unsafe { block.as_ptr().write(Self::new_uninit()) };
+        Ok(block)
+    }
+
+    /// # Safety
+    ///
+    /// `block` must be returned by `alloc` with the same allocator, and all its items
+    /// must be already moved out.
+    unsafe fn free(block: NonNull<Self>, alloc: &impl Allocator) {
+        ptr::drop_in_place(block.as_ptr());
+        alloc.deallocate(block.cast(), Self::LAYOUT);
+    }
+
     unsafe fn read(&self, slot_idx: usize) -> T {
         self.array
             .get_unchecked(slot_idx)
@@ -46,49 +69,98 @@ impl<T> Block<T> {
             .with_mut(|inner| inner.write(MaybeUninit::new(t)));
     }
 
+    /// Copy `items` into the slots starting from `slot_idx`.
+    unsafe fn write_slice(&self, items: &[T], slot_idx: usize)
+    where
+        T: Copy,
+    {
+        debug_assert!(slot_idx + items.len() <= BLOCK_SIZE);
+
+        // loom's cell is not transparent, and tracks each access.
+        if_loom! {
+            for (i, item) in items.iter().enumerate() {
+                self.write(*item, slot_idx + i);
+            }
+            return;
+        }
+
+        // `UnsafeCell` is transparent, so the slots can be viewed as a `[T]`.
+        let dst = self.array.as_ptr().add(slot_idx) as *mut T;
+        ptr::copy_nonoverlapping(items.as_ptr(), dst, items.len());
+    }
+
+    /// Move `len` items starting from `slot_idx` to the end of `buf`.
+    unsafe fn read_slice(&self, slot_idx: usize, len: usize, buf: &mut Vec<T>) {
+        debug_assert!(slot_idx + len <= BLOCK_SIZE);
+
+        // The same as `write_slice`.
+        if_loom! {
+            buf.extend((slot_idx..slot_idx + len).map(|idx| self.read(idx)));
+            return;
+        }
+
+        let src = self.array.as_ptr().add(slot_idx) as *const T;
+        let buf_len = buf.len();
+        ptr::copy_nonoverlapping(src, buf.as_mut_ptr().add(buf_len), len);
+        buf.set_len(buf_len + len);
+    }
+
+    unsafe fn get(&self, slot_idx: usize) -> *mut T {
+        self.array
+            .get_unchecked(slot_idx)
+            .with_mut(|inner| (*inner).as_mut_ptr())
+    }
+
     unsafe fn load_next_unchecked(&self) -> NonNull<Block<T>> {
         NonNull::new_unchecked(self.next.load(Ordering::Acquire))
     }
 }
 
-pub(crate) struct Queue<T> {
+/// The positions are `C`, atomics unless the queue is used by a `local` channel.
+pub(crate) struct Queue<T, C: Counter = AtomicUsize, A: Allocator = Global> {
     producer: UnsafeCell<NonNull<Block<T>>>,
-    producer_pos: AtomicUsize,
+    producer_pos: C,
     consumer: UnsafeCell<NonNull<Block<T>>>,
-    consumer_pos: AtomicUsize,
+    consumer_pos: C,
+    /// Allocates the blocks on the producer side, and frees them on the consumer side.
+    alloc: A,
 }
 
-unsafe impl<T: Send> Send for Queue<T> {}
+unsafe impl<T: Send, C: Send + Counter, A: Allocator> Send for Queue<T, C, A> {}
 
-unsafe impl<T: Send> Sync for Queue<T> {}
+unsafe impl<T: Send, C: Sync + Counter, A: Allocator> Sync for Queue<T, C, A> {}
 
-impl<T> Queue<T> {
+impl<T, C: Counter> Queue<T, C> {
     pub(crate) fn new() -> Self {
-        let block = Box::new(Block::new_uninit());
-        let block_ptr = Box::into_raw(block);
-        Self {
-            producer: UnsafeCell::new(NonNull::new(block_ptr).unwrap()),
-            producer_pos: AtomicUsize::new(0),
-            consumer: UnsafeCell::new(NonNull::new(block_ptr).unwrap()),
-            consumer_pos: AtomicUsize::new(0),
-        }
+        Self::new_in(Global).unwrap_or_else(|_| handle_alloc_error(Block::<T>::LAYOUT))
+    }
+}
+
+impl<T, C: Counter, A: Allocator> Queue<T, C, A> {
+    pub(crate) fn new_in(alloc: A) -> Result<Self, AllocError> {
+        let block = Block::alloc(&alloc)?;
+        Ok(Self {
+            producer: UnsafeCell::new(block),
+            producer_pos: C::default(),
+            consumer: UnsafeCell::new(block),
+            consumer_pos: C::default(),
+            alloc,
+        })
     }
 }
 
-impl<T> Drop for Queue<T> {
+impl<T, C: Counter, A: Allocator> Drop for Queue<T, C, A> {
     fn drop(&mut self) {
         unsafe {
             while self.try_pop().is_some() {}
 
             // drop the last block
-            self.consumer.with(|ptr| {
-                std::mem::drop(Box::from_raw((*ptr).as_ptr()));
-            })
+            self.consumer.with(|ptr| Block::free(*ptr, &self.alloc))
         }
     }
 }
 
-impl<T> Queue<T> {
+impl<T, C: Counter, A: Allocator> Queue<T, C, A> {
     pub(crate) fn is_empty(&self) -> bool {
         self.producer_pos.load(Ordering::Acquire) == self.consumer_pos.load(Ordering::Acquire)
     }
@@ -103,17 +175,82 @@ impl<T> Queue<T> {
         });
 
         if (next & BLOCK_MASK) < now_idx {
-            let next_ptr = Box::into_raw(Box::new(Block::new_uninit()));
-            self.producer.with_mut(|ptr| unsafe {
-                let refs = (*ptr).as_mut();
-                refs.next.store(next_ptr, Ordering::Release);
-                *ptr = NonNull::new_unchecked(next_ptr);
-            });
+            self.grow();
         }
 
         self.producer_pos.store(next, Ordering::Release);
     }
 
+    /// Copy all the items to the queue, then publish them with a single store.
+    pub(crate) unsafe fn extend_from_slice(&self, mut items: &[T])
+    where
+        T: Copy,
+    {
+        let mut now = self.producer_pos.load(Ordering::Acquire);
+
+        while !items.is_empty() {
+            let now_idx = now & BLOCK_MASK;
+            let n = cmp::min(items.len(), BLOCK_SIZE - now_idx);
+
+            self.producer.with(|ptr| unsafe {
+                (*ptr).as_ref().write_slice(&items[..n], now_idx);
+            });
+
+            now += n;
+            items = &items[n..];
+
+            // the same as `push`, link the next block once the last slot was written.
+            if now & BLOCK_MASK == 0 {
+                self.grow();
+            }
+        }
+
+        self.producer_pos.store(now, Ordering::Release);
+    }
+
+    /// Link a new block after the producer's block, and move the producer to it.
+    unsafe fn grow(&self) {
+        let next_ptr = Block::alloc(&self.alloc)
+            .unwrap_or_else(|_| handle_alloc_error(Block::<T>::LAYOUT))
+            .as_ptr();
+        self.producer.with_mut(|ptr| unsafe {
+            let refs = (*ptr).as_mut();
+            refs.next.store(next_ptr, Ordering::Release);
+            *ptr = NonNull::new_unchecked(next_ptr);
+        });
+    }
+
+    /// Returns a pointer to the head item without popping it.
+    ///
+    /// # Safety
+    ///
+    /// Only the consumer can call this method, and the pointer is only valid until the next pop.
+    pub(crate) unsafe fn peek(&self) -> Option<*mut T> {
+        if self.is_empty() {
+            None
+        } else {
+            let now = self.consumer_pos.load(Ordering::Acquire);
+            Some(
+                self.consumer
+                    .with(|ptr| (*ptr).as_ref().get(now & BLOCK_MASK)),
+            )
+        }
+    }
+
+    /// Returns an iterator over the items pushed so far, without popping them.
+    ///
+    /// # Safety
+    ///
+    /// Only the consumer can call this method, and must not pop while iterating.
+    pub(crate) unsafe fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            block: self.consumer.with(|ptr| *ptr),
+            pos: self.consumer_pos.load(Ordering::Acquire),
+            end: self.producer_pos.load(Ordering::Acquire),
+            _marker: PhantomData,
+        }
+    }
+
     pub(crate) unsafe fn try_pop(&self) -> Option<T> {
         if self.is_empty() {
             None
@@ -125,19 +262,85 @@ impl<T> Queue<T> {
             let ret = self.consumer.with(|ptr| (*ptr).as_ref().read(now_idx));
 
             if (next & BLOCK_MASK) < now_idx {
-                self.consumer.with_mut(|ptr| {
-                    let refs = (*ptr).as_ref();
-                    let old = *ptr;
-                    *ptr = refs.load_next_unchecked();
-
-                    std::mem::drop(Box::from_raw(old.as_ptr()));
-                })
+                self.shrink();
             }
 
             self.consumer_pos.store(next, Ordering::Release);
             Some(ret)
         }
     }
+
+    /// Move up to `limit` items to the end of `buf`, and release them with a single store.
+    pub(crate) unsafe fn pop_into(&self, buf: &mut Vec<T>, limit: usize) -> usize {
+        let mut now = self.consumer_pos.load(Ordering::Acquire);
+        let len = self.producer_pos.load(Ordering::Acquire) - now;
+        let count = cmp::min(len, limit);
+        let end = now + count;
+
+        buf.reserve(count);
+        while now != end {
+            let now_idx = now & BLOCK_MASK;
+            let n = cmp::min(end - now, BLOCK_SIZE - now_idx);
+
+            self.consumer
+                .with(|ptr| (*ptr).as_ref().read_slice(now_idx, n, buf));
+
+            now += n;
+            if now & BLOCK_MASK == 0 {
+                self.shrink();
+            }
+        }
+
+        self.consumer_pos.store(end, Ordering::Release);
+        count
+    }
+
+    /// Move the consumer to the next block, and free the drained one.
+    unsafe fn shrink(&self) {
+        self.consumer.with_mut(|ptr| {
+            let refs = (*ptr).as_ref();
+            let old = *ptr;
+            *ptr = refs.load_next_unchecked();
+
+            Block::free(old, &self.alloc);
+        })
+    }
+}
+
+pub(crate) struct Iter<'a, T> {
+    block: NonNull<Block<T>>,
+    pos: usize,
+    end: usize,
+    _marker: PhantomData<&'a T>,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.pos == self.end {
+            return None;
+        }
+
+        unsafe {
+            let now_idx = self.pos & BLOCK_MASK;
+            let item = &*self.block.as_ref().get(now_idx);
+            self.pos += 1;
+
+            // The producer links the next block before publishing the last slot of this one,
+            // so the next block must be there once we have read that slot.
+            if (self.pos & BLOCK_MASK) < now_idx {
+                self.block = self.block.as_ref().load_next_unchecked();
+            }
+
+            Some(item)
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.end - self.pos;
+        (len, Some(len))
+    }
 }
 
 #[cfg(all(test, loom))]
@@ -149,7 +352,7 @@ mod tests {
     #[test]
     fn push_pop() {
         loom::model(|| {
-            let queue = Arc::new(Queue::new());
+            let queue = Arc::new(Queue::<i32>::new());
             let queue1 = queue.clone();
             loom::thread::spawn(move || unsafe {
                 for i in 0..3 {
@@ -1,20 +1,28 @@
+use crate::allocator::{Allocator, Global};
 use crate::error::SendError;
+use crate::loom::{AtomicUsize, Counter};
+use crate::wait::{AsyncWaker, WaitStrategy};
 use crate::UnboundedSender;
+use core::pin::Pin;
+use core::task::{Context, Poll};
 use futures_sink::Sink;
-use std::pin::Pin;
-use std::task::{Context, Poll};
 
-pub struct UnboundedSenderWrapper<T> {
-    inner: Option<UnboundedSender<T>>,
+pub struct UnboundedSenderWrapper<
+    T,
+    W: WaitStrategy = AsyncWaker,
+    C: Counter = AtomicUsize,
+    A: Allocator = Global,
+> {
+    inner: Option<UnboundedSender<T, W, C, A>>,
 }
 
-impl<T> Sink<T> for UnboundedSenderWrapper<T> {
+impl<T, W: WaitStrategy, C: Counter, A: Allocator> Sink<T> for UnboundedSenderWrapper<T, W, C, A> {
     type Error = SendError;
 
     fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         self.inner
             .as_ref()
-            .and_then(|sender| (!sender.is_closed()).then(|| Poll::Ready(Ok(()))))
+            .and_then(|sender| (!sender.is_closed()).then_some(Poll::Ready(Ok(()))))
             .unwrap_or(Poll::Ready(Err(SendError::Disconnected)))
     }
 
@@ -35,8 +43,8 @@ impl<T> Sink<T> for UnboundedSenderWrapper<T> {
     }
 }
 
-impl<T> UnboundedSenderWrapper<T> {
-    pub fn new(sender: UnboundedSender<T>) -> Self {
+impl<T, W: WaitStrategy, C: Counter, A: Allocator> UnboundedSenderWrapper<T, W, C, A> {
+    pub fn new(sender: UnboundedSender<T, W, C, A>) -> Self {
         Self {
             inner: Some(sender),
         }
@@ -0,0 +1,269 @@
+use crate::atomic_waker::AtomicWaker;
+#[cfg(feature = "std")]
+use crate::blocking;
+#[cfg(feature = "std")]
+use crate::loom::thread;
+use core::fmt;
+use core::hint;
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::task::Waker;
+#[cfg(feature = "std")]
+use core::time::Duration;
+
+/// How one side of a bounded channel waits for the other side, chosen when the channel is
+/// created with [`channel_with_wait`].
+///
+/// Each side owns one instance. When the channel is not ready, the waiting side calls
+/// [`register`] with the waker of the current task, then checks the channel again before it
+/// returns `Poll::Pending`. The other side calls [`wake_by_ref`] after every send, pop or
+/// close, so it should be cheap when nobody waits. The blocking methods poll in a loop, and
+/// call [`park`] after every `Poll::Pending`.
+///
+/// [`AsyncWaker`] is what [`channel`] uses. The spinning strategies never touch an
+/// `AtomicWaker`: they wake the task right away, so the async methods keep polling, which is
+/// only reasonable when the task owns a core.
+///
+/// The halves of a channel can only be sent to another thread if the strategy is `Send` and
+/// `Sync`, which all of them are except the [`LocalWaker`] of the `local` channels.
+///
+/// [`LocalWaker`]: crate::local::LocalWaker
+/// [`channel_with_wait`]: crate::channel_with_wait
+/// [`channel`]: crate::channel
+/// [`register`]: WaitStrategy::register
+/// [`wake_by_ref`]: WaitStrategy::wake_by_ref
+/// [`park`]: WaitStrategy::park
+pub trait WaitStrategy {
+    /// Arranges for `waker` to be woken once the other side makes progress.
+    fn register(&self, waker: &Waker);
+
+    /// Called by the other side after it made progress.
+    fn wake_by_ref(&self);
+
+    /// Blocks the current thread after `register`, until the registered waker is woken,
+    /// the timeout elapses, or spuriously.
+    ///
+    /// By default, this parks the thread, which the wakers of the blocking methods unpark.
+    #[cfg(feature = "std")]
+    fn park(&self, timeout: Option<Duration>) {
+        blocking::park(timeout)
+    }
+}
+
+/// Registers the waker of the task in an `AtomicWaker`, which the other side wakes.
+#[derive(Default)]
+pub struct AsyncWaker {
+    waker: AtomicWaker,
+}
+
+impl WaitStrategy for AsyncWaker {
+    fn register(&self, waker: &Waker) {
+        self.waker.register(waker)
+    }
+
+    fn wake_by_ref(&self) {
+        self.waker.wake_by_ref()
+    }
+}
+
+impl Clone for AsyncWaker {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl fmt::Debug for AsyncWaker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AsyncWaker")
+    }
+}
+
+/// Polls again right away, the other side never does anything to wake us.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BusySpin;
+
+impl WaitStrategy for BusySpin {
+    fn register(&self, waker: &Waker) {
+        hint::spin_loop();
+        waker.wake_by_ref();
+    }
+
+    fn wake_by_ref(&self) {}
+}
+
+/// The same as [`BusySpin`], but yields the thread once every `n` polls.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct SpinThenYield {
+    spins: u32,
+    count: AtomicU32,
+}
+
+#[cfg(feature = "std")]
+impl SpinThenYield {
+    pub fn new(spins: u32) -> Self {
+        Self {
+            spins,
+            count: AtomicU32::new(0),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Clone for SpinThenYield {
+    fn clone(&self) -> Self {
+        Self::new(self.spins)
+    }
+}
+
+#[cfg(feature = "std")]
+impl WaitStrategy for SpinThenYield {
+    fn register(&self, waker: &Waker) {
+        // Only the waiting side touches the counter.
+        let count = self.count.load(Ordering::Relaxed);
+        if count < self.spins {
+            self.count.store(count + 1, Ordering::Relaxed);
+            hint::spin_loop();
+        } else {
+            self.count.store(0, Ordering::Relaxed);
+            thread::yield_now();
+        }
+        waker.wake_by_ref();
+    }
+
+    fn wake_by_ref(&self) {}
+}
+
+/// Spins for `n` polls, then registers the waker like [`AsyncWaker`], so the blocking
+/// methods park the thread.
+#[derive(Debug)]
+pub struct SpinThenPark {
+    spins: u32,
+    count: AtomicU32,
+    inner: AsyncWaker,
+}
+
+impl SpinThenPark {
+    pub fn new(spins: u32) -> Self {
+        Self {
+            spins,
+            count: AtomicU32::new(0),
+            inner: AsyncWaker::default(),
+        }
+    }
+}
+
+impl Default for SpinThenPark {
+    fn default() -> Self {
+        Self::new(64)
+    }
+}
+
+impl Clone for SpinThenPark {
+    fn clone(&self) -> Self {
+        Self::new(self.spins)
+    }
+}
+
+impl WaitStrategy for SpinThenPark {
+    fn register(&self, waker: &Waker) {
+        let count = self.count.load(Ordering::Relaxed);
+        if count < self.spins {
+            self.count.store(count + 1, Ordering::Relaxed);
+            hint::spin_loop();
+            waker.wake_by_ref();
+        } else {
+            self.count.store(0, Ordering::Relaxed);
+            self.inner.register(waker);
+        }
+    }
+
+    fn wake_by_ref(&self) {
+        self.inner.wake_by_ref()
+    }
+}
+
+#[cfg(all(target_os = "linux", feature = "futex", not(loom)))]
+pub use self::futex::Futex;
+
+#[cfg(all(target_os = "linux", feature = "futex", not(loom)))]
+mod futex {
+    use super::WaitStrategy;
+    use core::sync::atomic::{fence, AtomicBool, AtomicU32, Ordering};
+    use core::task::Waker;
+    use core::time::Duration;
+    use core::{fmt, ptr};
+
+    /// The blocking methods sleep on a futex word of this side, which the other side only
+    /// bumps when this side is armed, so there is no syscall while both sides are busy.
+    ///
+    /// A futex can't wake a task, so the async methods keep polling, like [`BusySpin`].
+    ///
+    /// [`BusySpin`]: super::BusySpin
+    #[derive(Default)]
+    pub struct Futex {
+        seq: AtomicU32,
+        /// The value of `seq` when this side was armed.
+        armed_seq: AtomicU32,
+        armed: AtomicBool,
+    }
+
+    impl Clone for Futex {
+        fn clone(&self) -> Self {
+            Self::default()
+        }
+    }
+
+    impl fmt::Debug for Futex {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("Futex")
+        }
+    }
+
+    impl WaitStrategy for Futex {
+        fn register(&self, waker: &Waker) {
+            self.armed_seq
+                .store(self.seq.load(Ordering::Acquire), Ordering::Relaxed);
+            self.armed.store(true, Ordering::Relaxed);
+            // Pairs with the fence in `wake_by_ref`: either the caller sees the progress
+            // when it checks the channel again, or the other side sees us armed.
+            fence(Ordering::SeqCst);
+            waker.wake_by_ref();
+        }
+
+        fn wake_by_ref(&self) {
+            fence(Ordering::SeqCst);
+            if self.armed.load(Ordering::Relaxed) && self.armed.swap(false, Ordering::Relaxed) {
+                self.seq.fetch_add(1, Ordering::Release);
+                unsafe {
+                    libc::syscall(
+                        libc::SYS_futex,
+                        self.seq.as_ptr(),
+                        libc::FUTEX_WAKE | libc::FUTEX_PRIVATE_FLAG,
+                        1,
+                    );
+                }
+            }
+        }
+
+        fn park(&self, timeout: Option<Duration>) {
+            let ts = timeout.map(|timeout| libc::timespec {
+                tv_sec: timeout.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+                tv_nsec: timeout.subsec_nanos() as libc::c_long,
+            });
+            let ts = ts
+                .as_ref()
+                .map_or(ptr::null(), |ts| ts as *const libc::timespec);
+
+            // Returns right away if `seq` was bumped since we were armed.
+            unsafe {
+                libc::syscall(
+                    libc::SYS_futex,
+                    self.seq.as_ptr(),
+                    libc::FUTEX_WAIT | libc::FUTEX_PRIVATE_FLAG,
+                    self.armed_seq.load(Ordering::Relaxed),
+                    ts,
+                );
+            }
+        }
+    }
+}
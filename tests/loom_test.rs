@@ -3,6 +3,7 @@
 use loom::future::block_on;
 use loom::thread;
 use spsc_rs::error::TryRecvError;
+use spsc_rs::FullPolicy;
 
 #[test]
 fn bounded_send_try_recv() {
@@ -74,3 +75,171 @@ fn bounded_closing_tx() {
         assert!(v.is_none());
     });
 }
+
+#[test]
+fn bounded_blocking_send_recv() {
+    loom::model(|| {
+        let (mut tx, mut rx) = spsc_rs::channel(1);
+
+        thread::spawn(move || {
+            tx.blocking_send(0).unwrap();
+            tx.blocking_send(1).unwrap();
+        });
+
+        assert_eq!(rx.blocking_recv(), Some(0));
+        assert_eq!(rx.blocking_recv(), Some(1));
+        assert_eq!(rx.blocking_recv(), None);
+    })
+}
+
+#[test]
+fn bounded_start_send_flush() {
+    loom::model(|| {
+        let (mut tx, mut rx) = spsc_rs::channel(1);
+
+        thread::spawn(move || {
+            block_on(async move {
+                tx.set_batch_send(true);
+                tx.start_send(0).unwrap();
+                tx.flush().await.unwrap();
+                tx.start_send(1).unwrap();
+            })
+        });
+
+        block_on(async move {
+            assert_eq!(rx.recv().await, Some(0));
+            assert_eq!(rx.recv().await, Some(1));
+            assert_eq!(rx.recv().await, None);
+        })
+    })
+}
+
+#[test]
+fn bounded_drop_oldest_race() {
+    loom::model(|| {
+        let (mut tx, mut rx) = spsc_rs::channel_with_policy(1, FullPolicy::DropOldest);
+        let handle = thread::spawn(move || {
+            for i in 0..3 {
+                tx.try_send(Box::new(i)).unwrap();
+            }
+            tx.discarded()
+        });
+
+        let mut last = None;
+        let mut received = 0;
+        loop {
+            match rx.try_recv_seq() {
+                Ok((seq, item)) => {
+                    assert_eq!(seq, *item as u64);
+                    assert!(last < Some(seq));
+                    last = Some(seq);
+                    received += 1;
+                }
+                Err(TryRecvError::Empty) => thread::yield_now(),
+                Err(TryRecvError::Disconnected) => break,
+            }
+        }
+
+        let discarded = handle.join().unwrap();
+        assert_eq!(received + discarded, 3);
+    })
+}
+
+#[test]
+fn bounded_cached_positions() {
+    loom::model(|| {
+        // single items go through the cached positions, and batches reload them.
+        let (mut tx, mut rx) = spsc_rs::channel(1);
+        thread::spawn(move || {
+            block_on(async move {
+                tx.send(0).await.unwrap();
+                tx.send_iter(1..3).await.unwrap();
+                tx.send(3).await.unwrap();
+            })
+        });
+
+        block_on(async move {
+            assert_eq!(rx.recv().await, Some(0));
+            let mut buf = Vec::new();
+            while buf.len() < 2 {
+                let limit = 2 - buf.len();
+                rx.recv_many(&mut buf, limit).await;
+            }
+            assert_eq!(buf, [1, 2]);
+            assert_eq!(rx.recv().await, Some(3));
+            assert_eq!(rx.recv().await, None);
+        })
+    })
+}
+
+#[test]
+fn bounded_deferred_positions() {
+    loom::model(|| {
+        // the staged item is published once the channel is full, and the batch of the
+        // receiver is released by `want_recv`.
+        let (mut tx, mut rx) = spsc_rs::channel(1);
+        thread::spawn(move || {
+            block_on(async move {
+                tx.set_batch_send(true);
+                tx.start_send(0).unwrap();
+                tx.send(1).await.unwrap();
+            })
+        });
+
+        block_on(async move {
+            rx.set_batch_recv(true);
+            let mut count = 0;
+            loop {
+                match rx.try_recv() {
+                    Ok(idx) => {
+                        assert_eq!(idx, count);
+                        count += 1;
+                    }
+                    Err(TryRecvError::Empty) => rx.want_recv().await,
+                    Err(TryRecvError::Disconnected) => break,
+                }
+            }
+
+            assert_eq!(count, 2);
+        })
+    })
+}
+
+#[test]
+fn exact_wrap_around() {
+    loom::model(|| {
+        // two slots, so the positions wrap around at 4, in the middle of the items.
+        let (mut tx, mut rx) = spsc_rs::exact_channel(1);
+        thread::spawn(move || {
+            block_on(async move {
+                for i in 0..5 {
+                    tx.send(i).await.unwrap();
+                }
+            })
+        });
+
+        block_on(async move {
+            for i in 0..5 {
+                assert_eq!(rx.recv_seq().await, Some((i, i)));
+            }
+            assert_eq!(rx.recv().await, None);
+        })
+    })
+}
+
+#[test]
+fn unbounded_slices() {
+    loom::model(|| {
+        // the blocks hold two items under loom, so both copies cross a block.
+        let (mut tx, mut rx) = spsc_rs::unbounded_channel();
+        thread::spawn(move || {
+            tx.extend_from_slice(&[0, 1, 2]).unwrap();
+        });
+
+        block_on(async move {
+            let mut buf = Vec::new();
+            while rx.recv_many(&mut buf, 3).await > 0 {}
+            assert_eq!(buf, [0, 1, 2]);
+        })
+    })
+}
@@ -1,4 +1,7 @@
-use spsc_rs::error::SendError;
+use spsc_rs::error::{SendError, TryRecvError};
+use spsc_rs::FullPolicy;
+use std::mem::MaybeUninit;
+use std::time::Duration;
 
 #[test]
 fn bounded_drop_test() {
@@ -34,7 +37,7 @@ fn bounded_close_test() {
 
     rx.close();
 
-    matches!(tx.start_send([1; 8]), Err(SendError::Disconnected));
+    matches!(tx.start_send([1; 8]), Err(e) if e.is_disconnected());
 
     let _ = rx.try_recv().unwrap();
     let _ = rx.try_recv().unwrap();
@@ -103,3 +106,1062 @@ fn unbounded_close_test() {
     let _ = rx.try_recv().unwrap();
     let _ = rx.try_recv().unwrap();
 }
+
+#[test]
+fn bounded_timeout_test() {
+    let (mut tx, mut rx) = spsc_rs::channel(1);
+
+    assert!(matches!(
+        rx.recv_timeout(Duration::from_millis(10)),
+        Err(TryRecvError::Empty)
+    ));
+
+    tx.send_timeout(1, Duration::from_millis(10)).unwrap();
+    let err = tx.send_timeout(2, Duration::from_millis(10)).unwrap_err();
+    assert!(err.is_full());
+    assert_eq!(err.into_inner(), 2);
+
+    assert_eq!(rx.recv_timeout(Duration::from_millis(10)).unwrap(), 1);
+
+    drop(tx);
+    assert!(matches!(
+        rx.recv_timeout(Duration::from_millis(10)),
+        Err(TryRecvError::Disconnected)
+    ));
+}
+
+#[test]
+fn unbounded_timeout_test() {
+    let (mut tx, mut rx) = spsc_rs::unbounded_channel();
+
+    assert!(matches!(
+        rx.recv_timeout(Duration::from_millis(10)),
+        Err(TryRecvError::Empty)
+    ));
+
+    tx.blocking_send(1).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_millis(10)).unwrap(), 1);
+
+    drop(tx);
+    assert!(matches!(
+        rx.recv_timeout(Duration::from_millis(10)),
+        Err(TryRecvError::Disconnected)
+    ));
+}
+
+#[test]
+fn bounded_reserve_slots_test() {
+    let (mut tx, mut rx) = spsc_rs::channel(8);
+    // move the positions so the reservation wraps around.
+    for i in 0..6 {
+        tx.start_send(i.to_string()).unwrap();
+    }
+    for _ in 0..6 {
+        let _ = rx.try_recv().unwrap();
+    }
+
+    let mut slots = tx.reserve_slots(16).unwrap();
+    assert_eq!(slots.len(), 8);
+    let (left, right) = slots.as_mut_slices();
+    assert_eq!((left.len(), right.len()), (2, 6));
+
+    slots.push("a".to_string()).unwrap();
+    unsafe {
+        slots.write_with(|slot| {
+            slot.write("b".to_string());
+        });
+    }
+    slots.push("c".to_string()).unwrap();
+    // "c" is written but not committed, it is dropped with the guard.
+    slots.commit(2);
+
+    assert_eq!(rx.try_recv().unwrap(), "a");
+    assert_eq!(rx.try_recv().unwrap(), "b");
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+
+    let mut slots = tx.reserve_slots(3).unwrap();
+    let (left, right) = slots.as_mut_slices();
+    for (i, slot) in left.iter_mut().chain(right).enumerate() {
+        slot.write(i.to_string());
+    }
+    unsafe { slots.assume_written(3) };
+    assert_eq!(slots.push("d".to_string()), Err("d".to_string()));
+    slots.commit(3);
+
+    for i in 0..3 {
+        assert_eq!(rx.try_recv().unwrap(), i.to_string());
+    }
+
+    let slots = tx.reserve_slots(8).unwrap();
+    assert_eq!(slots.len(), 8);
+    drop(slots);
+
+    while tx.start_send(String::new()).is_ok() {}
+    assert!(matches!(tx.reserve_slots(1), Err(SendError::Full)));
+}
+
+#[test]
+fn bounded_read_slices_test() {
+    let (mut tx, mut rx) = spsc_rs::channel(8);
+    // move the positions so the readable items wrap around.
+    for i in 0..6 {
+        tx.start_send(i.to_string()).unwrap();
+    }
+    rx.consume(6);
+
+    for i in 0..5 {
+        tx.start_send(i.to_string()).unwrap();
+    }
+
+    let (left, right) = rx.read_slices();
+    assert_eq!(left, ["0", "1"]);
+    assert_eq!(right, ["2", "3", "4"]);
+
+    let (left, _) = rx.read_slices_mut();
+    left[0].push('!');
+
+    assert_eq!(*rx.try_recv_ref().unwrap(), "0!");
+    rx.consume(3);
+    assert_eq!(rx.read_slices(), (&["4".to_string()][..], &[][..]));
+
+    {
+        let mut guard = rx.try_recv_ref().unwrap();
+        guard.push('?');
+        assert_eq!(*guard, "4?");
+    }
+    assert!(matches!(rx.try_recv_ref(), Err(TryRecvError::Empty)));
+
+    tx.start_send("5".to_string()).unwrap();
+    drop(tx);
+    assert_eq!(*rx.try_recv_ref().unwrap(), "5");
+    assert!(matches!(rx.try_recv_ref(), Err(TryRecvError::Disconnected)));
+}
+
+#[test]
+fn bounded_peek_test() {
+    let (mut tx, mut rx) = spsc_rs::channel(8);
+    assert!(rx.peek().is_none());
+
+    for i in 0..6 {
+        tx.start_send(i).unwrap();
+    }
+    rx.consume(4);
+    for i in 6..10 {
+        tx.start_send(i).unwrap();
+    }
+
+    assert_eq!(rx.peek(), Some(&4));
+    *rx.peek_mut().unwrap() = 40;
+    assert_eq!(
+        rx.iter_pending().copied().collect::<Vec<_>>(),
+        [40, 5, 6, 7, 8, 9]
+    );
+
+    assert_eq!(rx.recv_if(|i| *i > 100), None);
+    assert_eq!(rx.recv_if(|i| *i == 40), Some(40));
+    assert_eq!(rx.try_recv().unwrap(), 5);
+}
+
+#[test]
+fn unbounded_peek_test() {
+    let (mut tx, mut rx) = spsc_rs::unbounded_channel();
+    assert!(rx.peek().is_none());
+    assert_eq!(rx.iter_pending().count(), 0);
+
+    // multi block in this channel
+    for i in 0..300 {
+        tx.send(i).unwrap();
+    }
+    for _ in 0..100 {
+        let _ = rx.try_recv().unwrap();
+    }
+
+    assert_eq!(rx.peek(), Some(&100));
+    *rx.peek_mut().unwrap() = 1000;
+    assert!(rx
+        .iter_pending()
+        .copied()
+        .eq(Some(1000).into_iter().chain(101..300)));
+
+    assert_eq!(rx.recv_if(|i| *i == 100), None);
+    assert_eq!(rx.recv_if(|i| *i == 1000), Some(1000));
+
+    while rx.recv_if(|i| *i < 255).is_some() {}
+    assert_eq!(rx.peek(), Some(&255));
+    assert_eq!(rx.iter_pending().count(), 45);
+}
+
+#[test]
+fn bounded_send_slice_test() {
+    let (mut tx, mut rx) = spsc_rs::channel(8);
+    // move the positions so the copy wraps around.
+    assert_eq!(tx.send_slice(&[0; 6]).unwrap(), 6);
+    rx.consume(6);
+
+    let items: Vec<u32> = (0..10).collect();
+    assert_eq!(tx.send_slice(&items).unwrap(), 8);
+    assert!(matches!(tx.send_slice(&items), Err(SendError::Full)));
+    assert!(rx.iter_pending().copied().eq(0..8));
+}
+
+#[test]
+fn unbounded_extend_from_slice_test() {
+    let (mut tx, mut rx) = spsc_rs::unbounded_channel();
+
+    let items: Vec<u32> = (0..1000).collect();
+    tx.send(0).unwrap();
+    tx.extend_from_slice(&items[1..500]).unwrap();
+    tx.extend_from_slice(&[]).unwrap();
+    tx.extend_from_slice(&items[500..]).unwrap();
+
+    assert!(rx.iter_pending().copied().eq(0..1000));
+    for i in 0..1000 {
+        assert_eq!(rx.try_recv().unwrap(), i);
+    }
+
+    rx.close();
+    assert!(tx.extend_from_slice(&items).unwrap_err().is_disconnected());
+}
+
+#[test]
+fn bounded_recv_many_test() {
+    let (mut tx, mut rx) = spsc_rs::channel(8);
+    // move the positions so the items wrap around.
+    for i in 0..6 {
+        tx.start_send(i.to_string()).unwrap();
+    }
+    rx.consume(6);
+    for i in 0..7 {
+        tx.start_send(i.to_string()).unwrap();
+    }
+
+    let mut buf = vec!["x".to_string()];
+    assert_eq!(rx.try_recv_many(&mut buf, 0), 0);
+    assert_eq!(rx.try_recv_many(&mut buf, 5), 5);
+    assert_eq!(rx.try_recv_many(&mut buf, 5), 2);
+    assert_eq!(rx.try_recv_many(&mut buf, 5), 0);
+    assert_eq!(buf, ["x", "0", "1", "2", "3", "4", "5", "6"]);
+}
+
+#[test]
+fn unbounded_recv_many_test() {
+    let (mut tx, mut rx) = spsc_rs::unbounded_channel();
+
+    // multi block in this channel
+    for i in 0..1000 {
+        tx.send(i.to_string()).unwrap();
+    }
+    let _ = rx.try_recv().unwrap();
+
+    let mut buf = Vec::new();
+    assert_eq!(rx.try_recv_many(&mut buf, 300), 300);
+    assert_eq!(rx.try_recv_many(&mut buf, 1000), 699);
+    assert_eq!(rx.try_recv_many(&mut buf, 1000), 0);
+    assert!(buf
+        .iter()
+        .eq((1..1000).map(|i| i.to_string()).collect::<Vec<_>>().iter()));
+
+    tx.send("a".to_string()).unwrap();
+    drop(tx);
+    assert_eq!(rx.peek().unwrap(), "a");
+}
+
+#[test]
+fn bounded_try_send_test() {
+    let (mut tx, mut rx) = spsc_rs::channel(1);
+
+    tx.try_send("a".to_string()).unwrap();
+    let err = tx.try_send("b".to_string()).unwrap_err();
+    assert!(err.is_full());
+    let err = tx.start_send(err.into_inner()).unwrap_err();
+    assert!(err.is_full());
+    assert_eq!(err.into_inner(), "b");
+
+    assert_eq!(rx.try_recv().unwrap(), "a");
+    rx.close();
+
+    let err = tx.try_send("c".to_string()).unwrap_err();
+    assert!(err.is_disconnected());
+    assert_eq!(err.into_inner(), "c");
+}
+
+#[test]
+fn bounded_capacity_test() {
+    let (mut tx, rx) = spsc_rs::channel::<usize>(5);
+    assert_eq!((tx.capacity(), rx.capacity()), (8, 8));
+    for i in 0..8 {
+        tx.try_send(i).unwrap();
+    }
+    assert!(tx.try_send(8).unwrap_err().is_full());
+
+    for size in [1, 3, 5] {
+        let (mut tx, mut rx) = spsc_rs::exact_channel(size);
+        assert_eq!((tx.capacity(), rx.capacity()), (size, size));
+        // every slot holds an item, also after the positions wrap around.
+        for round in 0..3 {
+            let items = (round..round + 10).collect::<Vec<_>>();
+            assert_eq!(tx.send_slice(&items).unwrap(), size);
+            assert!(tx.try_send(0).unwrap_err().is_full());
+            let mut buf = Vec::new();
+            assert_eq!(rx.try_recv_many(&mut buf, usize::MAX), size);
+            assert!(buf.into_iter().eq(round..round + size));
+        }
+    }
+
+    let (tx, rx) = spsc_rs::channel::<usize>(0);
+    assert_eq!((tx.capacity(), rx.capacity()), (1, 1));
+}
+
+#[test]
+fn bounded_drop_newest_test() {
+    let (mut tx, mut rx) = spsc_rs::exact_channel_with_policy(3, FullPolicy::DropNewest);
+
+    for i in 0..5 {
+        tx.try_send(i.to_string()).unwrap();
+    }
+    assert_eq!(tx.discarded(), 2);
+
+    assert_eq!(rx.try_recv_seq().unwrap(), (0, "0".to_string()));
+    assert_eq!(rx.try_recv_seq().unwrap(), (1, "1".to_string()));
+    assert_eq!(rx.try_recv_seq().unwrap(), (2, "2".to_string()));
+    assert!(matches!(rx.try_recv_seq(), Err(TryRecvError::Empty)));
+
+    tx.try_send("5".to_string()).unwrap();
+    assert_eq!(rx.try_recv_seq().unwrap(), (5, "5".to_string()));
+    assert_eq!(rx.discarded(), 2);
+}
+
+#[test]
+fn bounded_drop_oldest_test() {
+    let (mut tx, mut rx) = spsc_rs::exact_channel_with_policy(3, FullPolicy::DropOldest);
+
+    for i in 0..5 {
+        tx.try_send(i.to_string()).unwrap();
+    }
+    assert_eq!(tx.discarded(), 2);
+
+    assert_eq!(rx.try_recv_seq().unwrap(), (2, "2".to_string()));
+
+    // the borrowed head item can't be evicted, the new items are discarded instead.
+    assert_eq!(rx.peek().unwrap(), "3");
+    tx.try_send("5".to_string()).unwrap();
+    tx.try_send("6".to_string()).unwrap();
+    assert_eq!(tx.discarded(), 3);
+
+    assert_eq!(rx.try_recv_seq().unwrap(), (3, "3".to_string()));
+    tx.try_send("7".to_string()).unwrap();
+    tx.try_send("8".to_string()).unwrap();
+    assert_eq!(tx.discarded(), 4);
+
+    let items = std::iter::from_fn(|| rx.try_recv_seq().ok()).collect::<Vec<_>>();
+    assert_eq!(
+        items,
+        vec![
+            (5, "5".to_string()),
+            (7, "7".to_string()),
+            (8, "8".to_string())
+        ]
+    );
+}
+
+#[test]
+fn bounded_drop_oldest_reserve_test() {
+    use std::task::{Context, Poll};
+
+    let waker = futures_util::task::noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let (mut tx, mut rx) = spsc_rs::channel_with_policy(2, FullPolicy::DropOldest);
+
+    tx.try_send(0).unwrap();
+    tx.try_send(1).unwrap();
+
+    // reserving in a full channel evicts the head item right away, so it is lost even
+    // though the permit is dropped without sending.
+    let Poll::Ready(Ok(_permit)) = tx.poll_reserve(&mut cx) else {
+        panic!("the permit is returned right away");
+    };
+    assert_eq!(tx.discarded(), 1);
+    assert_eq!(rx.try_recv_seq().unwrap(), (1, 1));
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+
+    // the dropped permit left its slot free.
+    let Poll::Ready(Ok(permit)) = tx.poll_reserve(&mut cx) else {
+        panic!("the permit is returned right away");
+    };
+    permit.send(2);
+    assert_eq!(tx.discarded(), 1);
+    assert_eq!(rx.try_recv_seq().unwrap(), (2, 2));
+
+    drop(rx);
+    assert!(matches!(
+        tx.poll_reserve(&mut cx),
+        Poll::Ready(Err(SendError::Disconnected))
+    ));
+}
+
+#[test]
+fn bounded_full_error_test() {
+    let (mut tx, mut rx) = spsc_rs::channel_with_policy(1, FullPolicy::Error);
+
+    tx.blocking_send(1).unwrap();
+    let err = tx.blocking_send(2).unwrap_err();
+    assert!(err.is_full());
+    assert_eq!(err.into_inner(), 2);
+    assert_eq!(tx.discarded(), 0);
+
+    assert_eq!(rx.try_recv_seq().unwrap(), (0, 1));
+    tx.blocking_send(3).unwrap();
+    assert_eq!(rx.try_recv_seq().unwrap(), (1, 3));
+}
+
+#[test]
+fn bounded_deferred_publish_test() {
+    use std::task::{Context, Poll};
+
+    let waker = futures_util::task::noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let (mut tx, mut rx) = spsc_rs::exact_channel(3);
+    tx.set_batch_send(true);
+
+    tx.start_send(0).unwrap();
+    tx.start_send(1).unwrap();
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+    // publishes the items, then waits for the receiver to take them.
+    assert!(tx.poll_flush(&mut cx).is_pending());
+    assert_eq!(rx.try_recv().unwrap(), 0);
+
+    tx.start_send(2).unwrap();
+    tx.start_send(3).unwrap();
+    assert!(tx.poll_ready(&mut cx).is_pending());
+    let mut buf = Vec::new();
+    assert_eq!(rx.try_recv_many(&mut buf, 3), 3);
+    assert_eq!(buf, [1, 2, 3]);
+    assert_eq!(tx.poll_flush(&mut cx), Poll::Ready(Ok(())));
+
+    // a method which sends right away publishes the staged items before its own.
+    tx.start_send(4).unwrap();
+    tx.try_send(5).unwrap();
+    assert_eq!(rx.try_recv().unwrap(), 4);
+    assert_eq!(rx.try_recv().unwrap(), 5);
+
+    // so does a full `start_send`.
+    for i in 6..9 {
+        tx.start_send(i).unwrap();
+    }
+    assert!(tx.start_send(9).unwrap_err().is_full());
+    assert_eq!(rx.try_recv().unwrap(), 6);
+
+    tx.start_send(9).unwrap();
+    drop(tx);
+    let items = std::iter::from_fn(|| rx.try_recv().ok()).collect::<Vec<_>>();
+    assert_eq!(items, [7, 8, 9]);
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Disconnected)));
+}
+
+#[test]
+fn bounded_batch_recv_test() {
+    use std::task::Context;
+
+    let waker = futures_util::task::noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let (mut tx, mut rx) = spsc_rs::exact_channel(3);
+    rx.set_batch_recv(true);
+
+    for i in 0..3 {
+        tx.try_send(i.to_string()).unwrap();
+    }
+    assert_eq!(rx.try_recv().unwrap(), "0");
+    assert_eq!(rx.try_recv().unwrap(), "1");
+    // the slots are only released by the commit.
+    assert!(tx.try_send("3".to_string()).unwrap_err().is_full());
+    rx.commit();
+    tx.try_send("3".to_string()).unwrap();
+    tx.try_send("4".to_string()).unwrap();
+
+    // and by `want_recv`.
+    assert_eq!(rx.peek().unwrap(), "2");
+    rx.consume(1);
+    assert!(tx.try_send("5".to_string()).unwrap_err().is_full());
+    assert!(rx.poll_want_recv(&mut cx).is_ready());
+    tx.try_send("5".to_string()).unwrap();
+
+    assert_eq!(*rx.try_recv_ref().unwrap(), "3");
+    rx.set_batch_recv(false);
+    tx.try_send("6".to_string()).unwrap();
+    assert_eq!(rx.try_recv().unwrap(), "4");
+    tx.try_send("7".to_string()).unwrap();
+
+    // the items received in batch mode are not dropped again with the channel.
+    rx.set_batch_recv(true);
+    assert_eq!(rx.try_recv().unwrap(), "5");
+    drop(tx);
+    drop(rx);
+}
+
+#[test]
+fn bip_channel_test() {
+    let (mut tx, mut rx) = spsc_rs::bip_channel(63);
+    assert_eq!(tx.max_record_len(), 28);
+
+    for len in [10, 20, 7] {
+        let mut slot = tx.try_reserve(len).unwrap();
+        slot.fill(len as u8);
+        slot.commit();
+    }
+    assert!(matches!(tx.try_reserve(12), Err(SendError::Full)));
+
+    // nothing is sent if the slot is dropped.
+    let _ = tx.try_reserve(1).unwrap();
+
+    for len in [10, 20] {
+        let record = rx.try_recv_ref().unwrap();
+        assert_eq!(&*record, &vec![len as u8; len][..]);
+    }
+
+    // the record doesn't fit at the end of the ring, so it starts over at the beginning.
+    let mut slot = tx.try_reserve(20).unwrap();
+    slot.copy_from_slice(&[40; 20]);
+    slot.commit();
+
+    assert_eq!(&*rx.try_recv_ref().unwrap(), &[7; 7]);
+    assert_eq!(&*rx.try_recv_ref().unwrap(), &[40; 20]);
+    assert!(matches!(rx.try_recv_ref(), Err(TryRecvError::Empty)));
+
+    drop(tx);
+    assert!(matches!(rx.try_recv_ref(), Err(TryRecvError::Disconnected)));
+}
+
+#[test]
+#[cfg(all(target_os = "linux", feature = "mirror", not(loom)))]
+#[cfg_attr(miri, ignore)]
+fn mirrored_channel_test() {
+    let (mut tx, mut rx) = spsc_rs::mirrored_channel::<u64>(100).unwrap();
+
+    // move the positions near the end of the ring, so the next items wrap around.
+    let mut n = 0;
+    while tx.try_send(0).is_ok() {
+        n += 1;
+    }
+    rx.consume(n - 10);
+    assert_eq!(rx.try_recv_many(&mut Vec::new(), 10), 10);
+
+    let items = (0..n as u64 - 1).collect::<Vec<_>>();
+    assert_eq!(tx.send_slice(&items).unwrap(), items.len());
+
+    let (left, right) = rx.read_slices();
+    assert_eq!(left, &items[..]);
+    assert!(right.is_empty());
+
+    // the slots wrap around too.
+    rx.consume(items.len());
+    let mut slots = tx.reserve_slots(usize::MAX).unwrap();
+    assert_eq!(slots.len(), n);
+    assert!(slots.as_mut_slices().1.is_empty());
+}
+
+#[test]
+#[cfg(all(target_os = "linux", feature = "mirror", not(loom)))]
+#[cfg_attr(miri, ignore)]
+fn mirrored_pipe_test() {
+    use std::task::{Context, Poll};
+
+    let waker = futures_util::task::noop_waker();
+    let mut cx = Context::from_waker(&waker);
+    let (mut writer, mut reader) = spsc_rs::mirrored_pipe(4096).unwrap();
+
+    let data = (0..6000).map(|i| i as u8).collect::<Vec<_>>();
+    for chunk in data.chunks(3000) {
+        let mut buf = Vec::new();
+        let mut written = 0;
+        while written < chunk.len() {
+            match writer.poll_write(&mut cx, &chunk[written..]) {
+                Poll::Ready(n) => written += n.unwrap(),
+                Poll::Pending => unreachable!(),
+            }
+        }
+
+        match reader.poll_fill_buf(&mut cx) {
+            Poll::Ready(bytes) => buf.extend_from_slice(bytes.unwrap()),
+            Poll::Pending => unreachable!(),
+        }
+        // all the bytes are returned at once, even when they wrap around.
+        assert_eq!(buf, chunk);
+        reader.consume(buf.len());
+    }
+}
+
+#[test]
+#[cfg(all(target_os = "linux", feature = "shm", not(loom)))]
+#[cfg_attr(miri, ignore)]
+fn shm_test() {
+    use spsc_rs::shm::Shm;
+    use std::os::fd::AsFd;
+
+    let shm = Shm::<u64>::anonymous(3).unwrap();
+    assert_eq!(shm.capacity(), 3);
+
+    // another mapping of the same memory, as the other process would do.
+    let other = Shm::<u64>::from_fd(shm.as_fd().try_clone_to_owned().unwrap()).unwrap();
+    let fd = shm.as_fd().try_clone_to_owned().unwrap();
+    assert!(Shm::<u32>::from_fd(fd).is_err());
+
+    let mut tx = shm.sender().unwrap();
+    let mut rx = other.receiver().unwrap();
+    assert!(other.sender().is_err());
+    assert!(shm.receiver().is_err());
+
+    for i in 0..3 {
+        tx.try_send(i).unwrap();
+    }
+    assert!(tx.try_send(3).unwrap_err().is_full());
+    assert!(matches!(
+        tx.send_timeout(3, Duration::from_millis(10)),
+        Err(e) if e.is_full()
+    ));
+
+    assert_eq!(rx.try_recv().unwrap(), 0);
+    tx.blocking_send(3).unwrap();
+    for i in 1..4 {
+        assert_eq!(rx.recv_timeout(Duration::from_millis(10)).unwrap(), i);
+    }
+    assert!(matches!(
+        rx.recv_timeout(Duration::from_millis(10)),
+        Err(TryRecvError::Empty)
+    ));
+
+    // every slot is used on every lap of the positions.
+    for lap in 0..3 {
+        for i in 0..3 {
+            tx.try_send(lap * 3 + i).unwrap();
+        }
+        assert!(tx.try_send(0).unwrap_err().is_full());
+        for i in 0..3 {
+            assert_eq!(rx.try_recv().unwrap(), lap * 3 + i);
+        }
+    }
+
+    tx.try_send(4).unwrap();
+    drop(tx);
+    assert_eq!(rx.blocking_recv(), Some(4));
+    assert_eq!(rx.blocking_recv(), None);
+}
+
+#[test]
+#[cfg(all(unix, feature = "remote", not(loom)))]
+#[cfg_attr(miri, ignore)]
+fn remote_test() {
+    use spsc_rs::remote::{channel_pair, RemoteReceiver, RemoteSendError};
+    use std::ffi::OsStr;
+    use std::io::Write;
+    use std::os::unix::ffi::OsStrExt;
+    use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
+
+    let (mut tx, mut rx) = channel_pair::<(u32, String)>(4).unwrap();
+    for i in 0..4 {
+        tx.try_send((i, i.to_string())).unwrap();
+    }
+    for i in 0..4 {
+        let item = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(item, (i, i.to_string()));
+    }
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+
+    tx.blocking_send((4, "last".into())).unwrap();
+    drop(tx);
+    assert_eq!(rx.blocking_recv(), Some((4, "last".into())));
+    assert_eq!(rx.blocking_recv(), None);
+
+    // the sender finds out the receiver is closed once its writes fail.
+    let (mut tx, mut rx) = channel_pair::<u64>(4).unwrap();
+    rx.close();
+    let start = std::time::Instant::now();
+    while !tx.is_closed() {
+        let _ = tx.send_timeout(0, Duration::from_millis(10));
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+    assert_eq!(tx.try_send(1).unwrap_err().into_inner(), 1);
+
+    // a frame which can't be decoded closes the channel.
+    let (mut a, b) = UnixStream::pair().unwrap();
+    let mut rx = RemoteReceiver::<String>::new(b, 4).unwrap();
+    a.write_all(&1u32.to_le_bytes()).unwrap();
+    a.write_all(&[0xff]).unwrap();
+    assert!(matches!(
+        rx.recv_timeout(Duration::from_secs(5)),
+        Err(TryRecvError::Disconnected)
+    ));
+    assert!(rx.is_closed());
+
+    // so does a frame longer than the limit, before it is read.
+    let (mut a, b) = UnixStream::pair().unwrap();
+    let mut rx = RemoteReceiver::<u8>::with_max_frame_len(b, 4, 1).unwrap();
+    a.write_all(&1u32.to_le_bytes()).unwrap();
+    a.write_all(&[7]).unwrap();
+    a.write_all(&u32::MAX.to_le_bytes()).unwrap();
+    assert_eq!(rx.recv_timeout(Duration::from_secs(5)).unwrap(), 7);
+    assert!(matches!(
+        rx.recv_timeout(Duration::from_secs(5)),
+        Err(TryRecvError::Disconnected)
+    ));
+
+    // an item which can't be encoded is given back.
+    let (mut tx, mut rx) = channel_pair::<PathBuf>(4).unwrap();
+    let path = PathBuf::from(OsStr::from_bytes(&[0xff]));
+    let err = tx.try_send(path.clone()).unwrap_err();
+    assert!(matches!(err, RemoteSendError::Encode(_)));
+    assert_eq!(err.into_inner(), path);
+
+    // the buffered items are still written after `close`.
+    tx.try_send("a".into()).unwrap();
+    tx.close();
+    assert!(tx.is_closed());
+    assert!(tx.try_send("b".into()).unwrap_err().is_disconnected());
+    assert_eq!(rx.blocking_recv(), Some("a".into()));
+    assert_eq!(rx.blocking_recv(), None);
+}
+
+#[test]
+#[cfg(all(target_os = "linux", feature = "eventfd", not(loom)))]
+#[cfg_attr(miri, ignore)]
+fn eventfd_test() {
+    use spsc_rs::EventfdReceiver;
+    use std::fs::File;
+    use std::io::{ErrorKind, Read};
+    use std::os::fd::AsFd;
+
+    fn readable<R>(rx: &EventfdReceiver<R>) -> bool {
+        let mut file = File::from(rx.as_fd().try_clone_to_owned().unwrap());
+        match file.read(&mut [0; 8]) {
+            Ok(n) => n == 8,
+            Err(err) => {
+                assert_eq!(err.kind(), ErrorKind::WouldBlock);
+                false
+            }
+        }
+    }
+
+    let (mut tx, rx) = spsc_rs::channel(4);
+    let mut rx = EventfdReceiver::new(rx).unwrap();
+    tx.try_send(1).unwrap();
+    // readable from the start, but not armed until the channel is drained.
+    assert!(readable(&rx));
+    tx.try_send(2).unwrap();
+    assert!(!readable(&rx));
+
+    assert_eq!(rx.try_recv().unwrap(), 1);
+    assert_eq!(rx.try_recv().unwrap(), 2);
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+    tx.try_send(3).unwrap();
+    tx.try_send(4).unwrap();
+    assert!(readable(&rx));
+    // only the first item after arming writes the eventfd.
+    assert!(!readable(&rx));
+
+    assert_eq!(rx.try_recv().unwrap(), 3);
+    // the deref'ed receiver works as usual.
+    assert_eq!(rx.peek(), Some(&4));
+    assert_eq!(rx.try_recv().unwrap(), 4);
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+    drop(tx);
+    assert!(readable(&rx));
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Disconnected)));
+
+    let (mut tx, rx) = spsc_rs::unbounded_channel();
+    let mut rx = EventfdReceiver::new(rx).unwrap();
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+    tx.send(1).unwrap();
+    assert!(readable(&rx));
+    assert_eq!(rx.try_recv().unwrap(), 1);
+}
+
+#[test]
+fn bounded_wait_strategy_test() {
+    use spsc_rs::{BusySpin, SpinThenPark, SpinThenYield, WaitStrategy};
+
+    fn check<W: WaitStrategy + Clone>(wait: W) {
+        let (mut tx, mut rx) = spsc_rs::channel_with_wait(4, wait);
+        for i in 0..4 {
+            tx.try_send(i).unwrap();
+        }
+        assert!(tx.try_send(4).unwrap_err().is_full());
+        assert!(matches!(
+            tx.send_timeout(4, Duration::from_millis(1)),
+            Err(e) if e.is_full()
+        ));
+
+        for i in 0..4 {
+            assert_eq!(rx.blocking_recv(), Some(i));
+        }
+        assert!(matches!(
+            rx.recv_timeout(Duration::from_millis(1)),
+            Err(TryRecvError::Empty)
+        ));
+        drop(tx);
+        assert_eq!(rx.blocking_recv(), None);
+    }
+
+    check(BusySpin);
+    check(SpinThenYield::new(4));
+    check(SpinThenPark::new(4));
+    #[cfg(all(target_os = "linux", feature = "futex", not(loom)))]
+    check(spsc_rs::Futex::default());
+}
+
+#[test]
+fn set_notify_test() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn counter() -> (Arc<AtomicUsize>, Arc<dyn Fn() + Send + Sync>) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let inner = count.clone();
+        (
+            count,
+            Arc::new(move || {
+                inner.fetch_add(1, Ordering::Relaxed);
+            }),
+        )
+    }
+
+    let (mut tx, mut rx) = spsc_rs::channel(1);
+    let (received, notify) = counter();
+    rx.set_notify(notify);
+    assert_eq!(received.load(Ordering::Relaxed), 0);
+
+    tx.try_send(1).unwrap();
+    assert_eq!(received.load(Ordering::Relaxed), 1);
+    // fires once until the receiver finds the channel empty again.
+    assert!(tx.try_send(2).unwrap_err().is_full());
+    assert_eq!(rx.try_recv().unwrap(), 1);
+    tx.try_send(2).unwrap();
+    assert_eq!(received.load(Ordering::Relaxed), 1);
+    assert_eq!(rx.try_recv().unwrap(), 2);
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+    tx.try_send(3).unwrap();
+    assert_eq!(received.load(Ordering::Relaxed), 2);
+
+    let (sent, notify) = counter();
+    tx.set_notify(notify);
+    assert_eq!(sent.load(Ordering::Relaxed), 0);
+    assert!(tx.try_send(4).unwrap_err().is_full());
+    assert_eq!(rx.blocking_recv(), Some(3));
+    assert_eq!(sent.load(Ordering::Relaxed), 1);
+
+    // in the batch receive mode, the sender is only notified once the room is released.
+    let (mut tx, mut rx) = spsc_rs::channel(1);
+    rx.set_batch_recv(true);
+    tx.try_send(1).unwrap();
+    let (sent, notify) = counter();
+    tx.set_notify(notify);
+    assert_eq!(rx.try_recv().unwrap(), 1);
+    assert_eq!(sent.load(Ordering::Relaxed), 0);
+    rx.commit();
+    assert_eq!(sent.load(Ordering::Relaxed), 1);
+
+    // already ready, so it fires right away.
+    let (mut tx, mut rx) = spsc_rs::unbounded_channel();
+    tx.send(1).unwrap();
+    let (received, notify) = counter();
+    rx.set_notify(notify);
+    assert_eq!(received.load(Ordering::Relaxed), 1);
+    assert_eq!(rx.try_recv().unwrap(), 1);
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+    tx.send(2).unwrap();
+    assert_eq!(received.load(Ordering::Relaxed), 2);
+
+    let (closed, notify) = counter();
+    tx.set_notify(notify);
+    assert_eq!(closed.load(Ordering::Relaxed), 0);
+    rx.close();
+    assert_eq!(closed.load(Ordering::Relaxed), 1);
+}
+
+#[test]
+fn local_channel_test() {
+    let (mut tx, mut rx) = spsc_rs::local::exact_channel(3);
+    for i in 0..3 {
+        tx.try_send(i).unwrap();
+    }
+    assert!(tx.try_send(3).unwrap_err().is_full());
+    assert_eq!(rx.peek(), Some(&0));
+    assert_eq!(*rx.try_recv_ref().unwrap(), 0);
+    assert_eq!(rx.try_recv().unwrap(), 1);
+
+    // wraps around the end of the ring.
+    assert_eq!(tx.send_slice(&[3, 4, 5]).unwrap(), 2);
+    assert_eq!(rx.iter_pending().copied().collect::<Vec<_>>(), [2, 3, 4]);
+    let mut buf = Vec::new();
+    assert_eq!(rx.try_recv_many(&mut buf, 2), 2);
+    assert_eq!(buf, [2, 3]);
+    drop(tx);
+    assert_eq!(rx.try_recv().unwrap(), 4);
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Disconnected)));
+
+    let (mut tx, mut rx) = spsc_rs::local::unbounded_channel();
+    for i in 0..300 {
+        tx.send(i).unwrap();
+    }
+    drop(tx);
+    let mut buf = Vec::new();
+    assert_eq!(rx.try_recv_many(&mut buf, 200), 200);
+    assert_eq!(rx.recv_if(|item| *item == 200), Some(200));
+    assert_eq!(rx.iter_pending().count(), 99);
+    assert!(rx.is_closed());
+}
+
+#[test]
+fn static_channel_test() {
+    static CHANNEL: spsc_rs::StaticChannel<String, 3> = spsc_rs::StaticChannel::new();
+
+    let (mut tx, mut rx) = CHANNEL.split().unwrap();
+    assert!(CHANNEL.split().is_none());
+    for i in 0..3 {
+        tx.try_send(i.to_string()).unwrap();
+    }
+    assert!(tx.try_send("3".to_string()).unwrap_err().is_full());
+    assert_eq!(rx.try_recv().unwrap(), "0");
+    tx.try_send("3".to_string()).unwrap();
+    assert_eq!(rx.read_slices().0.len() + rx.read_slices().1.len(), 3);
+    drop(tx);
+    for i in 1..4 {
+        assert_eq!(rx.try_recv().unwrap(), i.to_string());
+    }
+    assert!(matches!(rx.try_recv(), Err(TryRecvError::Disconnected)));
+}
+
+#[test]
+fn channel_in_test() {
+    let mut buf = [const { MaybeUninit::uninit() }; 2];
+    let mut channel = spsc_rs::channel_in(&mut buf);
+
+    let (mut tx, mut rx) = channel.split();
+    tx.try_send(Box::new(1)).unwrap();
+    tx.try_send(Box::new(2)).unwrap();
+    assert!(tx.try_send(Box::new(3)).unwrap_err().is_full());
+    assert_eq!(*rx.try_recv().unwrap(), 1);
+    tx.try_send(Box::new(3)).unwrap();
+    drop(rx);
+    assert!(tx.is_closed());
+    drop(tx);
+
+    // split again, the items left in the channel are still there.
+    let (mut tx, mut rx) = channel.split();
+    assert!(!tx.is_closed());
+    assert_eq!(*rx.try_recv().unwrap(), 2);
+    tx.try_send(Box::new(4)).unwrap();
+    rx.set_batch_recv(true);
+    drop((tx, rx));
+
+    // the new receiver doesn't keep the batch mode of the old one.
+    let (mut tx, mut rx) = channel.split();
+    assert_eq!(*rx.try_recv().unwrap(), 3);
+    tx.try_send(Box::new(5)).unwrap();
+    assert_eq!(*rx.try_recv().unwrap(), 4);
+    tx.try_send(Box::new(6)).unwrap();
+    // the remaining items are dropped with the channel.
+}
+
+#[test]
+fn custom_allocator_test() {
+    use spsc_rs::allocator::{AllocError, Allocator, Global};
+    use std::alloc::Layout;
+    use std::ptr::NonNull;
+    use std::sync::atomic::{AtomicIsize, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct Counting(Arc<AtomicIsize>);
+
+    unsafe impl Allocator for Counting {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<u8>, AllocError> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.0.fetch_sub(1, Ordering::Relaxed);
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    let alloc = Counting::default();
+    let (mut tx, mut rx) = spsc_rs::exact_channel_with_alloc(5, alloc.clone()).unwrap();
+    assert_eq!(alloc.0.load(Ordering::Relaxed), 1);
+    for i in 0..5 {
+        tx.try_send(i.to_string()).unwrap();
+    }
+    assert!(tx.try_send("5".to_string()).unwrap_err().is_full());
+    assert_eq!(rx.try_recv().unwrap(), "0");
+    drop((tx, rx));
+    assert_eq!(alloc.0.load(Ordering::Relaxed), 0);
+
+    let (mut tx, mut rx) = spsc_rs::unbounded_channel_with_alloc(alloc.clone()).unwrap();
+    for i in 0..300 {
+        tx.send(i.to_string()).unwrap();
+    }
+    // one block per 128 items.
+    assert_eq!(alloc.0.load(Ordering::Relaxed), 3);
+    for i in 0..200 {
+        assert_eq!(rx.try_recv().unwrap(), i.to_string());
+    }
+    assert_eq!(alloc.0.load(Ordering::Relaxed), 2);
+    drop((tx, rx));
+    assert_eq!(alloc.0.load(Ordering::Relaxed), 0);
+
+    struct Failing;
+
+    unsafe impl Allocator for Failing {
+        fn allocate(&self, _: Layout) -> Result<NonNull<u8>, AllocError> {
+            Err(AllocError)
+        }
+
+        unsafe fn deallocate(&self, _: NonNull<u8>, _: Layout) {
+            unreachable!()
+        }
+    }
+
+    assert!(spsc_rs::channel_with_alloc::<u8, _>(16, Failing).is_err());
+    assert!(spsc_rs::unbounded_channel_with_alloc::<u8, _>(Failing).is_err());
+    // zero sized items never allocate.
+    assert!(spsc_rs::channel_with_alloc::<(), _>(16, Failing).is_ok());
+}
+
+#[test]
+#[cfg(all(target_os = "linux", feature = "mmap", not(loom)))]
+#[cfg_attr(miri, ignore)]
+fn mmap_allocator_test() {
+    use spsc_rs::allocator::{HugePages, MmapAllocator};
+
+    fn check(alloc: MmapAllocator) {
+        let (mut tx, mut rx) = spsc_rs::channel_with_alloc(1 << 12, alloc).unwrap();
+        assert_eq!(tx.send_slice(&[7u64; 1 << 12]).unwrap(), 1 << 12);
+        assert_eq!(rx.read_slices().0.len() + rx.read_slices().1.len(), 1 << 12);
+        rx.consume(1 << 12);
+
+        let (mut tx, mut rx) = spsc_rs::unbounded_channel_with_alloc(alloc).unwrap();
+        for i in 0..1000u64 {
+            tx.send(i).unwrap();
+        }
+        for i in 0..1000u64 {
+            assert_eq!(rx.try_recv().unwrap(), i);
+        }
+    }
+
+    check(MmapAllocator::new());
+    check(MmapAllocator::new().prefault(true));
+    check(MmapAllocator::new().mlock(true));
+    check(
+        MmapAllocator::new()
+            .huge_pages(HugePages::Transparent)
+            .prefault(true)
+            .mlock(true),
+    );
+
+    // the huge page pool is usually empty, so the channel may not be created.
+    let explicit = MmapAllocator::new().huge_pages(HugePages::Explicit);
+    if let Ok((mut tx, mut rx)) = spsc_rs::exact_channel_with_alloc(1000, explicit) {
+        tx.try_send(1u8).unwrap();
+        assert_eq!(rx.try_recv().unwrap(), 1);
+    }
+}
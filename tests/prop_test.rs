@@ -0,0 +1,140 @@
+#![cfg(not(loom))]
+
+use proptest::prelude::*;
+use spsc_rs::error::TryRecvError;
+use spsc_rs::FullPolicy;
+use std::collections::VecDeque;
+
+#[derive(Debug, Clone)]
+enum Op {
+    TrySend(usize),
+    StartSend(usize),
+    SendSlice(usize),
+    TryRecv(usize),
+    RecvMany(usize),
+    Consume(usize),
+}
+
+fn op() -> impl Strategy<Value = Op> {
+    prop_oneof![
+        (1..8usize).prop_map(Op::TrySend),
+        (1..8usize).prop_map(Op::StartSend),
+        (1..8usize).prop_map(Op::SendSlice),
+        (1..8usize).prop_map(Op::TryRecv),
+        (1..8usize).prop_map(Op::RecvMany),
+        (1..8usize).prop_map(Op::Consume),
+    ]
+}
+
+proptest! {
+    // The positions of an exact channel wrap around at twice its slots, so a few hundred
+    // operations on a small channel cross the wrap many times.
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn exact_channel_matches_a_queue(size in 1..10usize, ops in prop::collection::vec(op(), 1..300)) {
+        let (mut tx, mut rx) = spsc_rs::exact_channel::<u64>(size);
+        let mut model = VecDeque::new();
+        let mut next = 0u64;
+
+        for op in ops {
+            match op {
+                Op::TrySend(n) | Op::StartSend(n) => {
+                    for _ in 0..n {
+                        let res = if matches!(op, Op::TrySend(_)) {
+                            tx.try_send(next)
+                        } else {
+                            tx.start_send(next)
+                        };
+                        match res {
+                            Ok(()) => {
+                                model.push_back(next);
+                                next += 1;
+                            }
+                            Err(err) => {
+                                prop_assert!(err.is_full());
+                                prop_assert_eq!(model.len(), size);
+                            }
+                        }
+                    }
+                    tx.commit();
+                }
+                Op::SendSlice(n) => {
+                    let items = (next..next + n as u64).collect::<Vec<_>>();
+                    match tx.send_slice(&items) {
+                        Ok(sent) => {
+                            prop_assert_eq!(sent, n.min(size - model.len()));
+                            model.extend(&items[..sent]);
+                            next += sent as u64;
+                        }
+                        Err(_) => prop_assert_eq!(model.len(), size),
+                    }
+                }
+                Op::TryRecv(n) => {
+                    for _ in 0..n {
+                        match rx.try_recv_seq() {
+                            Ok((seq, item)) => {
+                                prop_assert_eq!(Some(item), model.pop_front());
+                                prop_assert_eq!(seq, item);
+                            }
+                            Err(err) => {
+                                prop_assert!(matches!(err, TryRecvError::Empty));
+                                prop_assert!(model.is_empty());
+                            }
+                        }
+                    }
+                }
+                Op::RecvMany(n) => {
+                    let mut buf = Vec::new();
+                    let received = rx.try_recv_many(&mut buf, n);
+                    prop_assert_eq!(received, n.min(model.len()));
+                    let expected = model.drain(..received).collect::<Vec<_>>();
+                    prop_assert_eq!(buf, expected);
+                }
+                Op::Consume(n) => {
+                    let (left, right) = rx.read_slices();
+                    let readable = left.iter().chain(right).copied().collect::<Vec<_>>();
+                    prop_assert_eq!(&readable, &model.iter().copied().collect::<Vec<_>>());
+
+                    let n = n.min(model.len());
+                    rx.consume(n);
+                    model.drain(..n);
+                }
+            }
+        }
+    }
+
+    // Discarded items still take a sequence number, which must keep counting once the
+    // positions wrap around.
+    #[test]
+    #[cfg_attr(miri, ignore)]
+    fn exact_channel_sequence_numbers(
+        size in 1..6usize,
+        ops in prop::collection::vec((0..8usize, 0..8usize), 1..200),
+    ) {
+        let (mut tx, mut rx) = spsc_rs::exact_channel_with_policy::<u64>(size, FullPolicy::DropNewest);
+        let mut model = VecDeque::new();
+        let mut next = 0u64;
+        let mut received = 0;
+
+        for (sends, recvs) in ops {
+            for _ in 0..sends {
+                tx.try_send(next).unwrap();
+                if model.len() < size {
+                    model.push_back(next);
+                }
+                next += 1;
+            }
+            for _ in 0..recvs {
+                match rx.try_recv_seq() {
+                    Ok((seq, item)) => {
+                        prop_assert_eq!(Some(item), model.pop_front());
+                        prop_assert_eq!(seq, item);
+                        received += 1;
+                    }
+                    Err(_) => prop_assert!(model.is_empty()),
+                }
+            }
+        }
+        prop_assert_eq!(tx.discarded() as u64, next - model.len() as u64 - received);
+    }
+}
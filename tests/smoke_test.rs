@@ -1,5 +1,5 @@
 use futures_util::SinkExt;
-use spsc_rs::error::TryRecvError;
+use spsc_rs::error::{SendError, TryRecvError};
 use spsc_rs::SenderWrapper;
 use std::future::Future;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -142,3 +142,604 @@ fn spsc_test() {
         receive_test_framework(10000, 100, send_sequence, receive_sequence);
     }
 }
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn blocking_test() {
+    // blocking sender, async receiver
+    for _ in 0..10 {
+        let (mut tx, rx) = spsc_rs::channel(2);
+        let t = thread::spawn(move || {
+            for x in 0..10000 {
+                tx.blocking_send(x).unwrap();
+            }
+        });
+        block_on(receive_sequence(10000, rx));
+        t.join().unwrap();
+    }
+
+    // async sender, blocking receiver
+    for _ in 0..10 {
+        let (tx, mut rx) = spsc_rs::channel(2);
+        let t = thread::spawn(move || block_on(send_sequence(10000, tx)));
+        let mut n = 0;
+        while let Some(i) = rx.blocking_recv() {
+            assert_eq!(i, n);
+            n += 1;
+        }
+        assert_eq!(n, 10000);
+        t.join().unwrap();
+    }
+
+    // unbounded, blocking receiver
+    for _ in 0..10 {
+        let (mut tx, mut rx) = spsc_rs::unbounded_channel();
+        let t = thread::spawn(move || {
+            for x in 0..10000 {
+                tx.send(x).unwrap();
+            }
+        });
+        let mut n = 0;
+        while let Some(i) = rx.blocking_recv() {
+            assert_eq!(i, n);
+            n += 1;
+        }
+        assert_eq!(n, 10000);
+        t.join().unwrap();
+    }
+}
+
+async fn receive_ref_sequence(amt: u32, mut rx: spsc_rs::P2Receiver<u32>) {
+    let mut n = 0;
+    while let Some(i) = rx.recv_ref().await {
+        assert_eq!(*i, n);
+        n += 1;
+    }
+
+    assert_eq!(n, amt);
+}
+
+async fn receive_slices_sequence(amt: u32, mut rx: spsc_rs::P2Receiver<u32>) {
+    let mut n = 0;
+    loop {
+        let (left, right) = rx.read_slices();
+        let len = left.len() + right.len();
+        for i in left.iter().chain(right) {
+            assert_eq!(*i, n);
+            n += 1;
+        }
+
+        if len == 0 {
+            if rx.is_closed() && rx.read_slices().0.is_empty() {
+                break;
+            }
+            rx.want_recv().await;
+        } else {
+            rx.consume(len);
+        }
+    }
+
+    assert_eq!(n, amt);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn zero_copy_test() {
+    for _ in 0..COUNT {
+        receive_test_framework(10000, 2, send_sequence, receive_ref_sequence);
+    }
+
+    for _ in 0..COUNT {
+        receive_test_framework(10000, 100, batch_sequence, receive_slices_sequence);
+    }
+}
+
+async fn send_iter_sequence(n: u32, mut sender: spsc_rs::P2Sender<u32>) {
+    sender.send_iter(0..n).await.unwrap();
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn send_iter_test() {
+    for _ in 0..COUNT {
+        receive_test_framework(10000, 2, send_iter_sequence, receive_sequence);
+    }
+
+    for _ in 0..COUNT {
+        receive_test_framework(10000, 100, send_iter_sequence, try_receive_sequence);
+    }
+}
+
+async fn receive_many_sequence(amt: u32, mut rx: spsc_rs::P2Receiver<u32>) {
+    let mut n = 0;
+    let mut buf = Vec::new();
+    while rx.recv_many(&mut buf, 64).await > 0 {
+        for i in buf.drain(..) {
+            assert_eq!(i, n);
+            n += 1;
+        }
+    }
+
+    assert_eq!(n, amt);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn recv_many_test() {
+    for _ in 0..COUNT {
+        receive_test_framework(10000, 2, send_sequence, receive_many_sequence);
+    }
+
+    for _ in 0..COUNT {
+        receive_test_framework(10000, 100, batch_sequence, receive_many_sequence);
+    }
+
+    // unbounded
+    for _ in 0..10 {
+        let (mut tx, mut rx) = spsc_rs::unbounded_channel();
+        let t = thread::spawn(move || {
+            for x in 0..10000 {
+                tx.send(x).unwrap();
+            }
+        });
+        block_on(async move {
+            let mut n = 0;
+            let mut buf = Vec::new();
+            while rx.recv_many(&mut buf, 64).await > 0 {
+                for i in buf.drain(..) {
+                    assert_eq!(i, n);
+                    n += 1;
+                }
+            }
+            assert_eq!(n, 10000);
+        });
+        t.join().unwrap();
+    }
+}
+
+async fn reserve_sequence(n: u32, mut sender: spsc_rs::P2Sender<u32>) {
+    for x in 0..n {
+        sender.reserve().await.unwrap().send(x);
+    }
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn reserve_test() {
+    for _ in 0..COUNT {
+        receive_test_framework(10000, 2, reserve_sequence, receive_sequence);
+    }
+
+    block_on(async {
+        let (mut tx, mut rx) = spsc_rs::channel::<u32>(1);
+        // nothing is sent if the permit is dropped.
+        let _ = tx.reserve().await.unwrap();
+        assert!(matches!(rx.try_recv(), Err(TryRecvError::Empty)));
+
+        rx.close();
+        assert!(matches!(tx.reserve().await, Err(SendError::Disconnected)));
+    });
+}
+
+#[test]
+#[cfg(feature = "tokio")]
+#[cfg_attr(miri, ignore)]
+fn pipe_test() {
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt};
+
+    let data = (0..100_000u32).map(|i| i as u8).collect::<Vec<_>>();
+    let rt = Builder::new_multi_thread().build().unwrap();
+
+    let (mut writer, mut reader) = spsc_rs::pipe(100);
+    let expected = data.clone();
+    let handle = rt.spawn(async move {
+        let mut buf = Vec::new();
+        reader.read_to_end(&mut buf).await.unwrap();
+        assert_eq!(buf, expected);
+    });
+
+    block_on(async move {
+        for chunk in data.chunks(333) {
+            writer.write_all(chunk).await.unwrap();
+        }
+        writer.shutdown().await.unwrap();
+    });
+    rt.block_on(handle).unwrap();
+
+    block_on(async {
+        let (mut writer, reader) = spsc_rs::pipe(64);
+        writer.write_all(b"hello\nworld").await.unwrap();
+        drop(writer);
+
+        // the reader is an `AsyncBufRead` by itself.
+        let mut lines = reader.lines();
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "hello");
+        assert_eq!(lines.next_line().await.unwrap().unwrap(), "world");
+        assert!(lines.next_line().await.unwrap().is_none());
+    });
+
+    block_on(async {
+        let (mut writer, reader) = spsc_rs::pipe(64);
+        drop(reader);
+        let err = writer.write_all(b"hello").await.unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::BrokenPipe);
+    });
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn bip_channel_test() {
+    const RECORDS: usize = 10000;
+
+    for _ in 0..COUNT {
+        let (mut tx, mut rx) = spsc_rs::bip_channel(100);
+        let max = tx.max_record_len();
+
+        let handle = thread::spawn(move || {
+            block_on(async move {
+                for i in 0..RECORDS {
+                    let len = i % (max + 1);
+                    let mut slot = tx.reserve(len).await.unwrap();
+                    slot.fill(i as u8);
+                    slot.commit();
+                }
+            })
+        });
+
+        block_on(async move {
+            let mut i = 0;
+            while let Some(record) = rx.recv_ref().await {
+                assert_eq!(record.len(), i % (max + 1));
+                assert!(record.iter().all(|b| *b == i as u8));
+                i += 1;
+            }
+            assert_eq!(i, RECORDS);
+        });
+
+        handle.join().unwrap();
+    }
+}
+
+#[test]
+#[cfg(all(target_os = "linux", feature = "shm", not(loom)))]
+#[cfg_attr(miri, ignore)]
+fn shm_test() {
+    use spsc_rs::shm::Shm;
+
+    const AMT: u64 = 100_000;
+    let name = format!("/spsc-rs-test-{}", std::process::id());
+    let shm = Shm::<[u64; 2]>::create(&name, 64).unwrap();
+    let other = Shm::<[u64; 2]>::open(&name).unwrap();
+    Shm::<[u64; 2]>::unlink(&name).unwrap();
+
+    // a blocking sender and an async receiver, through two different mappings.
+    let mut tx = shm.sender().unwrap();
+    let handle = thread::spawn(move || {
+        for i in 0..AMT {
+            tx.blocking_send([i, !i]).unwrap();
+        }
+    });
+
+    let mut rx = other.receiver().unwrap();
+    block_on(async move {
+        let mut count = 0;
+        while let Some([i, j]) = rx.recv().await {
+            assert_eq!(i, count);
+            assert_eq!(j, !count);
+            count += 1;
+        }
+        assert_eq!(count, AMT);
+    });
+    handle.join().unwrap();
+
+    // and the other way around.
+    let shm = Shm::<u64>::anonymous(4).unwrap();
+    let mut tx = shm.sender().unwrap();
+    let mut rx = shm.receiver().unwrap();
+    let handle = thread::spawn(move || {
+        block_on(async move {
+            for i in 0..AMT {
+                tx.send(i).await.unwrap();
+            }
+        })
+    });
+    for i in 0..AMT {
+        assert_eq!(rx.blocking_recv(), Some(i));
+    }
+    assert_eq!(rx.blocking_recv(), None);
+    handle.join().unwrap();
+}
+
+#[test]
+#[cfg(all(unix, feature = "remote", not(loom)))]
+#[cfg_attr(miri, ignore)]
+fn remote_test() {
+    use futures_util::StreamExt;
+    use spsc_rs::remote::channel_pair;
+
+    const AMT: u32 = 10_000;
+    let (mut tx, rx) = channel_pair::<Vec<u32>>(16).unwrap();
+
+    let handle = thread::spawn(move || {
+        block_on(async move {
+            for i in 0..AMT {
+                // through the `Sink` impl, flushing once in a while.
+                tx.feed(vec![i; (i % 8) as usize]).await.unwrap();
+                if i % 100 == 0 {
+                    SinkExt::<Vec<u32>>::flush(&mut tx).await.unwrap();
+                }
+            }
+            SinkExt::<Vec<u32>>::close(&mut tx).await.unwrap();
+        })
+    });
+
+    block_on(async move {
+        let items: Vec<_> = rx.collect().await;
+        assert_eq!(items.len(), AMT as usize);
+        for (i, item) in items.into_iter().enumerate() {
+            assert_eq!(item, vec![i as u32; i % 8]);
+        }
+    });
+    handle.join().unwrap();
+}
+
+#[test]
+#[cfg(all(target_os = "linux", feature = "mio", not(loom)))]
+#[cfg_attr(miri, ignore)]
+fn eventfd_mio_test() {
+    use mio::{Events, Interest, Poll, Token};
+    use spsc_rs::EventfdReceiver;
+
+    const AMT: u32 = 100_000;
+    let (mut tx, rx) = spsc_rs::channel(64);
+    let mut rx = EventfdReceiver::new(rx).unwrap();
+
+    let mut poll = Poll::new().unwrap();
+    let mut events = Events::with_capacity(4);
+    poll.registry()
+        .register(&mut rx, Token(0), Interest::READABLE)
+        .unwrap();
+
+    let handle = thread::spawn(move || {
+        for i in 0..AMT {
+            tx.blocking_send(i).unwrap();
+        }
+    });
+
+    let mut count = 0;
+    'outer: loop {
+        poll.poll(&mut events, Some(Duration::from_secs(5)))
+            .unwrap();
+        assert!(!events.is_empty(), "missed a wake up");
+
+        loop {
+            match rx.try_recv() {
+                Ok(i) => {
+                    assert_eq!(i, count);
+                    count += 1;
+                }
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => break 'outer,
+            }
+        }
+    }
+    assert_eq!(count, AMT);
+    handle.join().unwrap();
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn wait_strategy_test() {
+    use spsc_rs::{AsyncWaker, BusySpin, SpinThenPark, SpinThenYield, WaitStrategy};
+
+    fn check<W: WaitStrategy + Clone + Send + Sync + 'static>(wait: W, amt: u32) {
+        // a blocking sender with an async receiver.
+        let (mut tx, mut rx) = spsc_rs::channel_with_wait(16, wait.clone());
+        let handle = thread::spawn(move || {
+            for i in 0..amt {
+                tx.blocking_send(i).unwrap();
+            }
+        });
+        block_on(async move {
+            for i in 0..amt {
+                assert_eq!(rx.recv().await, Some(i));
+            }
+            assert_eq!(rx.recv().await, None);
+        });
+        handle.join().unwrap();
+
+        // an async sender with a blocking receiver.
+        let (mut tx, mut rx) = spsc_rs::exact_channel_with_wait(10, wait);
+        let handle = thread::spawn(move || {
+            block_on(async move {
+                for i in 0..amt {
+                    tx.send(i).await.unwrap();
+                }
+            })
+        });
+        for i in 0..amt {
+            assert_eq!(rx.blocking_recv(), Some(i));
+        }
+        assert_eq!(rx.blocking_recv(), None);
+        handle.join().unwrap();
+    }
+
+    check(AsyncWaker::default(), 100_000);
+    check(SpinThenPark::default(), 100_000);
+    // the spinning strategies burn whole time slices when the threads share a core.
+    check(BusySpin, 1_000);
+    check(SpinThenYield::new(16), 10_000);
+    #[cfg(all(target_os = "linux", feature = "futex", not(loom)))]
+    check(spsc_rs::Futex::default(), 100_000);
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn set_notify_test() {
+    use std::sync::mpsc;
+
+    const AMT: u32 = 100_000;
+
+    // a foreign event loop, which only drains the channel when it is told to.
+    let (events, queue) = mpsc::channel();
+    let (mut tx, mut rx) = spsc_rs::channel(16);
+    let event = events.clone();
+    rx.set_notify(Arc::new(move || {
+        let _ = event.send("recv");
+    }));
+    let (utx, mut urx) = spsc_rs::unbounded_channel();
+    urx.set_notify(Arc::new(move || {
+        let _ = events.send("unbounded");
+    }));
+
+    let handle = thread::spawn(move || {
+        let mut utx = utx;
+        for i in 0..AMT {
+            tx.blocking_send(i).unwrap();
+            utx.send(i).unwrap();
+        }
+    });
+
+    let (mut count, mut ucount) = (0, 0);
+    let (mut closed, mut uclosed) = (false, false);
+    while !(closed && uclosed) {
+        match queue.recv_timeout(Duration::from_secs(5)).unwrap() {
+            "recv" => loop {
+                // `try_recv_many` lets the blocked sender know there is room.
+                let mut buf = Vec::new();
+                if rx.try_recv_many(&mut buf, 64) == 0 {
+                    // nothing left, `try_recv` arms the callback again.
+                    match rx.try_recv() {
+                        Ok(i) => buf.push(i),
+                        Err(TryRecvError::Empty) => break,
+                        Err(TryRecvError::Disconnected) => {
+                            closed = true;
+                            break;
+                        }
+                    }
+                }
+                for i in buf {
+                    assert_eq!(i, count);
+                    count += 1;
+                }
+            },
+            _ => loop {
+                match urx.try_recv() {
+                    Ok(i) => {
+                        assert_eq!(i, ucount);
+                        ucount += 1;
+                    }
+                    Err(TryRecvError::Empty) => break,
+                    Err(TryRecvError::Disconnected) => {
+                        uclosed = true;
+                        break;
+                    }
+                }
+            },
+        }
+    }
+    assert_eq!((count, ucount), (AMT, AMT));
+    handle.join().unwrap();
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn local_channel_test() {
+    use futures_util::StreamExt;
+    use tokio::task::{self, LocalSet};
+
+    const AMT: u32 = 100_000;
+
+    let local = LocalSet::new();
+    block_on(local.run_until(async {
+        let (tx, rx) = spsc_rs::local::channel(16);
+        let sender = task::spawn_local(async move {
+            let mut tx = spsc_rs::local::SenderWrapper::new(tx);
+            for i in 0..AMT {
+                tx.feed(i).await.unwrap();
+            }
+            tx.close().await.unwrap();
+        });
+        let items = rx.collect::<Vec<_>>().await;
+        sender.await.unwrap();
+        assert!(items.into_iter().eq(0..AMT));
+
+        let (mut tx, mut rx) = spsc_rs::local::exact_channel(7);
+        let sender = task::spawn_local(async move {
+            tx.send_iter(0..AMT).await.unwrap();
+        });
+        let mut count = 0;
+        while let Some(item) = rx.recv_ref().await {
+            assert_eq!(*item, count);
+            count += 1;
+        }
+        sender.await.unwrap();
+        assert_eq!(count, AMT);
+
+        let (tx, mut rx) = spsc_rs::local::unbounded_channel();
+        let sender = task::spawn_local(async move {
+            let mut tx = spsc_rs::local::UnboundedSenderWrapper::new(tx);
+            for i in 0..AMT {
+                tx.send(i).await.unwrap();
+                if i % 1000 == 0 {
+                    task::yield_now().await;
+                }
+            }
+        });
+        let mut buf = Vec::new();
+        while rx.recv_many(&mut buf, 64).await > 0 {}
+        sender.await.unwrap();
+        assert!(buf.into_iter().eq(0..AMT));
+    }));
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn static_channel_test() {
+    static CHANNEL: spsc_rs::StaticChannel<u32, 16> = spsc_rs::StaticChannel::new();
+
+    let amt = 100_000;
+    let (mut tx, mut rx) = CHANNEL.split().unwrap();
+    let handle = thread::spawn(move || {
+        block_on(async move {
+            for i in 0..amt {
+                tx.send(i).await.unwrap();
+            }
+        })
+    });
+    block_on(async move {
+        for i in 0..amt {
+            assert_eq!(rx.recv().await, Some(i));
+        }
+        assert_eq!(rx.recv().await, None);
+    });
+    handle.join().unwrap();
+}
+
+#[test]
+#[cfg_attr(miri, ignore)]
+fn channel_in_test() {
+    let amt = 100_000;
+    let mut buf = [const { std::mem::MaybeUninit::uninit() }; 10];
+    let mut channel = spsc_rs::channel_in(&mut buf);
+    let (mut tx, mut rx) = channel.split();
+
+    thread::scope(|s| {
+        s.spawn(move || {
+            for i in 0..amt {
+                tx.blocking_send(i).unwrap();
+            }
+        });
+        block_on(async move {
+            let mut buf = Vec::new();
+            let mut next = 0;
+            while rx.recv_many(&mut buf, 4).await > 0 {
+                for i in buf.drain(..) {
+                    assert_eq!(i, next);
+                    next += 1;
+                }
+            }
+            assert_eq!(next, amt);
+        });
+    });
+}